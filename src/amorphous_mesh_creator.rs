@@ -1,25 +1,48 @@
 
-use ggez::graphics::{self, Color, DrawMode, Mesh, MeshBuilder};
+use crate::personality::Personality;
+use ggez::graphics::{Color, DrawMode, Mesh, MeshBuilder};
 use ggez::{Context, GameResult};
-use mint::Point2;
+use mint::{Point2, Vector2};
 use noise::{NoiseFn, Perlin};
 
-pub fn create_amorphous_mesh(
-    ctx: &mut Context,
+// Short-lived pushes on the blob's shape from the outside
+pub struct Distortion<'a> {
+    // Pulls the leading side out towards whatever is dragging the blob
+    pub stretch_direction: Vector2<f32>,
+    pub stretch_amount: f32,
+    // Extra wobble, e.g. right after a hit
+    pub squash: f32,
+    // An extra radial offset per point (e.g. from a `SoftBody`); missing entries count as zero
+    pub deformation: &'a [f32],
+}
+
+impl Distortion<'_> {
+    pub fn none() -> Self {
+        Distortion {
+            stretch_direction: Vector2 { x: 0.0, y: 0.0 },
+            stretch_amount: 0.0,
+            squash: 0.0,
+            deformation: &[],
+        }
+    }
+}
+
+// The blob's outline around the origin, one point per `personality.point_count`
+// at evenly spaced angles starting from the positive x axis.
+pub fn amorphous_points(
     size: f32,
     noise: &Perlin,
     time: f32,
     in_proximity: bool,
     normalized_distance_from_player: f32,
-) -> GameResult<Mesh> {
-    let mut builder = MeshBuilder::new();
-
- 
-    let num_points = 20;
+    personality: &Personality,
+    distortion: &Distortion,
+) -> Vec<Point2<f32>> {
+    let num_points = personality.point_count;
     let angle_step = (2.0 * std::f32::consts::PI) / num_points as f32;
     let (noise_scale_start, noise_scale_end) = (0.3, 0.7);  // not in proximity to in proximity
     let (time_scale_start, time_scale_end) = (0.2, 0.25); 
-    let modulated_time = time.cos() * 4.0; 
+    let modulated_time = (time * personality.wobble_speed).cos() * 4.0; 
     let noise_scale = noise_scale_start + (noise_scale_end - noise_scale_start) * (normalized_distance_from_player *2.0);
     let time_scale = time_scale_start + (time_scale_end - time_scale_start) * normalized_distance_from_player;
    // println!("noise_scale{}", noise_scale);
@@ -28,7 +51,9 @@ pub fn create_amorphous_mesh(
 
     let base_radius = size / 2.0;
     let min_radius = base_radius * 0.4;
-    let noise_amplitude = base_radius * normalized_distance_from_player;
+    // A squashed blob (e.g. one that was just hit) wobbles much harder for a moment
+    let noise_amplitude =
+        base_radius * (normalized_distance_from_player * personality.wobble_amplitude + distortion.squash);
 
     // First pass: calculate points for the blob
     for i in 0..num_points {
@@ -37,7 +62,8 @@ pub fn create_amorphous_mesh(
         let noise_y = (angle.sin() * noise_scale + modulated_time * time_scale) as f64;
         let noise_value = noise.get([noise_x, noise_y]) as f32;
         let noise_offset = noise_value * noise_amplitude;
-        let radius = (base_radius + noise_offset).max(min_radius);
+        let dent = distortion.deformation.get(i).copied().unwrap_or(0.0);
+        let radius = (base_radius + noise_offset + dent).max(min_radius);
         let x = radius * angle.cos();
         let y = radius * angle.sin();
 
@@ -47,10 +73,32 @@ pub fn create_amorphous_mesh(
     // Second pass: smooth the points
     let smoothed_points = smooth_points(&points);
 
+    // Third pass: pull the leading side out towards whatever is dragging the blob
+    stretch_points(&smoothed_points, distortion.stretch_direction, distortion.stretch_amount)
+}
+
+// Blends two outlines with matching point counts; 0.0 gives `from`, 1.0 gives `to`
+pub fn morph_points(from: &[Point2<f32>], to: &[Point2<f32>], t: f32) -> Vec<Point2<f32>> {
+    if t <= 0.0 || from.len() != to.len() {
+        return from.to_vec();
+    }
+    from.iter()
+        .zip(to.iter())
+        .map(|(a, b)| Point2 {
+            x: a.x + (b.x - a.x) * t,
+            y: a.y + (b.y - a.y) * t,
+        })
+        .collect()
+}
+
+// White filled polygon; the actual color is applied when drawing
+pub fn build_outline_mesh(ctx: &mut Context, points: &[Point2<f32>]) -> GameResult<Mesh> {
+    let mut builder = MeshBuilder::new();
+
     // Build the polygon with smoothed points
     builder.polygon(
         DrawMode::fill(),
-        &smoothed_points,
+        points,
         Color::from_rgb(255, 255, 255),
     )?;
 
@@ -81,4 +129,23 @@ fn smooth_points(points: &[Point2<f32>]) -> Vec<Point2<f32>> {
     }
 
     smoothed_points
-}
\ No newline at end of file
+}
+
+// Pushes points on the side facing `direction` further out, so the blob reads as a teardrop.
+// `direction` is expected to be normalized; an amount of 0.0 leaves the points untouched.
+fn stretch_points(points: &[Point2<f32>], direction: Vector2<f32>, amount: f32) -> Vec<Point2<f32>> {
+    if amount <= 0.0 {
+        return points.to_vec();
+    }
+
+    points
+        .iter()
+        .map(|point| {
+            let along = (point.x * direction.x + point.y * direction.y).max(0.0);
+            Point2 {
+                x: point.x + direction.x * along * amount,
+                y: point.y + direction.y * along * amount,
+            }
+        })
+        .collect()
+}
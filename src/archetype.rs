@@ -0,0 +1,392 @@
+use crate::behavior::{BehaviorKind, DEFAULT_BEHAVIORS, WANDER_LEASH_RADIUS, WANDER_SPEED};
+use crate::eye_cluster::{EyeLayout, EyePlacement, GazeMode};
+use crate::personality::PersonalityRanges;
+use serde::{Deserialize, Serialize};
+
+// More than this crowd the outline until nothing reads as a tentacle or an eye
+pub const MAX_TENTACLE_COUNT: usize = 12;
+pub const MAX_EYE_COUNT: usize = 8;
+// Outlines with more points than this cost more than they add
+pub const MAX_POINT_COUNT: usize = 64;
+// Eye radius as a fraction of the collectible's size; a quarter already fills half the body
+pub const MAX_EYE_SIZE: f32 = 0.25;
+
+// Describes a kind of collectible. Every collectible carries a copy of its
+// archetype so that anything spawned from it (e.g. split children) stays the same kind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archetype {
+    pub name: String,
+    // `None` means the collectible is gathered on first touch
+    pub hit_points: Option<u32>,
+    // How many children a split produces, picked at random between the two
+    pub split_count_min: u32,
+    pub split_count_max: u32,
+    // Child size relative to the parent
+    pub split_scale: f32,
+    // Collectibles whose children would be smaller than this are collected instead of split
+    pub min_split_size: f32,
+    // Each collectible of this kind gets a personality drawn from these ranges
+    pub personality: PersonalityRanges,
+    pub eye_layout: EyeLayout,
+    pub tentacle_count: usize,
+    // Collecting one grows the player's magnet radius by this much; 0.0 for ordinary collectibles
+    pub magnet_bonus: f32,
+    // Built-in behaviors every collectible of this kind starts with, in the order they run
+    pub behaviors: Vec<BehaviorKind>,
+    // How far an idle collectible wanders from its spawn point, and how quickly
+    pub wander_leash_radius: f32,
+    pub wander_speed: f32,
+}
+
+impl Archetype {
+    pub fn can_split(&self, size: f32) -> bool {
+        self.split_count_max > 0 && size * self.split_scale >= self.min_split_size
+    }
+}
+
+impl Default for Archetype {
+    fn default() -> Self {
+        Archetype {
+            name: "blob".to_string(),
+            hit_points: None,
+            split_count_min: 2,
+            split_count_max: 3,
+            split_scale: 0.6,
+            min_split_size: 40.0,
+            personality: PersonalityRanges::default(),
+            eye_layout: EyeLayout::default(),
+            tentacle_count: 3,
+            magnet_bonus: 0.0,
+            behaviors: DEFAULT_BEHAVIORS.to_vec(),
+            wander_leash_radius: WANDER_LEASH_RADIUS,
+            wander_speed: WANDER_SPEED,
+        }
+    }
+}
+
+// An archetype as written in data files: a name plus whichever fields differ from the base
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArchetypeParams {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit_points: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split_count_min: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split_count_max: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub split_scale: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_split_size: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tentacle_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eye_count: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eye_placement: Option<EyePlacement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eye_gaze: Option<GazeMode>,
+    // Eye radii as fractions of the collectible's size, reused in order if there are fewer than eyes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eye_sizes: Option<Vec<f32>>,
+    // Average blinks per second; 0 never blinks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blink_rate: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub magnet_bonus: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub behaviors: Option<Vec<BehaviorKind>>,
+    // Personality ranges, each as [min, max]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wobble_amplitude: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wobble_speed: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tentacle_noise_scale: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub point_count: Option<[usize; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wander_leash_radius: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wander_speed: Option<f32>,
+}
+
+impl ArchetypeParams {
+    pub fn build(&self, base: &Archetype) -> Archetype {
+        let mut archetype = base.clone();
+        archetype.name = self.name.clone();
+        if self.hit_points.is_some() {
+            archetype.hit_points = self.hit_points;
+        }
+        if let Some(split_count_min) = self.split_count_min {
+            archetype.split_count_min = split_count_min;
+        }
+        if let Some(split_count_max) = self.split_count_max {
+            archetype.split_count_max = split_count_max;
+        }
+        if let Some(split_scale) = self.split_scale {
+            archetype.split_scale = split_scale;
+        }
+        if let Some(min_split_size) = self.min_split_size {
+            archetype.min_split_size = min_split_size;
+        }
+        if let Some(tentacle_count) = self.tentacle_count {
+            archetype.tentacle_count = tentacle_count;
+        }
+        if let Some(eye_count) = self.eye_count {
+            archetype.eye_layout.count = eye_count;
+        }
+        if let Some(eye_placement) = self.eye_placement {
+            archetype.eye_layout.placement = eye_placement;
+        }
+        if let Some(eye_gaze) = self.eye_gaze {
+            archetype.eye_layout.gaze = eye_gaze;
+        }
+        if let Some(eye_sizes) = &self.eye_sizes {
+            archetype.eye_layout.sizes = eye_sizes.clone();
+        }
+        if let Some(blink_rate) = self.blink_rate {
+            archetype.eye_layout.blink_rate = blink_rate;
+        }
+        if let Some(magnet_bonus) = self.magnet_bonus {
+            archetype.magnet_bonus = magnet_bonus;
+        }
+        if let Some(behaviors) = &self.behaviors {
+            archetype.behaviors = behaviors.clone();
+        }
+        if let Some([min, max]) = self.wobble_amplitude {
+            archetype.personality.wobble_amplitude = min..max;
+        }
+        if let Some([min, max]) = self.wobble_speed {
+            archetype.personality.wobble_speed = min..max;
+        }
+        if let Some([min, max]) = self.tentacle_noise_scale {
+            archetype.personality.tentacle_noise_scale = min..max;
+        }
+        if let Some([min, max]) = self.point_count {
+            // Both ends included, as written
+            archetype.personality.point_count = min..max + 1;
+        }
+        if let Some(wander_leash_radius) = self.wander_leash_radius {
+            archetype.wander_leash_radius = wander_leash_radius;
+        }
+        if let Some(wander_speed) = self.wander_speed {
+            archetype.wander_speed = wander_speed;
+        }
+        archetype
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("archetype name must not be empty".to_string());
+        }
+        if let (Some(min), Some(max)) = (self.split_count_min, self.split_count_max) {
+            if min > max {
+                return Err(format!(
+                    "archetype '{}': split_count_min ({}) is above split_count_max ({})",
+                    self.name, min, max
+                ));
+            }
+        }
+        if let Some(split_scale) = self.split_scale {
+            if !(split_scale > 0.0 && split_scale < 1.0) {
+                return Err(format!(
+                    "archetype '{}': split_scale must be between 0.0 and 1.0, got {}",
+                    self.name, split_scale
+                ));
+            }
+        }
+        if let Some(min_split_size) = self.min_split_size {
+            // Anything else lets collectibles split forever, so the field is never cleared
+            if !(min_split_size.is_finite() && min_split_size > 0.0) {
+                return Err(format!(
+                    "archetype '{}': min_split_size must be positive, got {}",
+                    self.name, min_split_size
+                ));
+            }
+        }
+        if let Some(tentacle_count) = self.tentacle_count {
+            if tentacle_count > MAX_TENTACLE_COUNT {
+                return Err(format!(
+                    "archetype '{}': tentacle_count must be at most {}, got {}",
+                    self.name, MAX_TENTACLE_COUNT, tentacle_count
+                ));
+            }
+        }
+        if let Some(eye_count) = self.eye_count {
+            if !(1..=MAX_EYE_COUNT).contains(&eye_count) {
+                return Err(format!(
+                    "archetype '{}': eye_count must be between 1 and {}, got {}",
+                    self.name, MAX_EYE_COUNT, eye_count
+                ));
+            }
+        }
+        if let Some(eye_sizes) = &self.eye_sizes {
+            if eye_sizes.is_empty() {
+                return Err(format!("archetype '{}': eye_sizes must not be empty", self.name));
+            }
+            if let Some(size) = eye_sizes.iter().find(|size| !(**size > 0.0 && **size <= MAX_EYE_SIZE)) {
+                return Err(format!(
+                    "archetype '{}': eye sizes must be above 0.0 and at most {}, got {}",
+                    self.name, MAX_EYE_SIZE, size
+                ));
+            }
+        }
+        if let Some(blink_rate) = self.blink_rate {
+            if !blink_rate.is_finite() || blink_rate < 0.0 {
+                return Err(format!(
+                    "archetype '{}': blink_rate must be 0 or more, got {}",
+                    self.name, blink_rate
+                ));
+            }
+        }
+        let float_ranges = [
+            ("wobble_amplitude", self.wobble_amplitude),
+            ("wobble_speed", self.wobble_speed),
+            ("tentacle_noise_scale", self.tentacle_noise_scale),
+        ];
+        for (field, range) in float_ranges {
+            if let Some([min, max]) = range {
+                if !(min.is_finite() && max.is_finite() && min >= 0.0 && min <= max) {
+                    return Err(format!(
+                        "archetype '{}': {} must be [min, max] with 0 <= min <= max, got [{}, {}]",
+                        self.name, field, min, max
+                    ));
+                }
+            }
+        }
+        if let Some([min, max]) = self.point_count {
+            if !(3 <= min && min <= max && max <= MAX_POINT_COUNT) {
+                return Err(format!(
+                    "archetype '{}': point_count must be [min, max] with 3 <= min <= max <= {}, got [{}, {}]",
+                    self.name, MAX_POINT_COUNT, min, max
+                ));
+            }
+        }
+        for (field, value) in [("wander_leash_radius", self.wander_leash_radius), ("wander_speed", self.wander_speed)] {
+            if let Some(value) = value {
+                if !value.is_finite() || value < 0.0 {
+                    return Err(format!("archetype '{}': {} must be 0 or more, got {}", self.name, field, value));
+                }
+            }
+        }
+        if let Some(magnet_bonus) = self.magnet_bonus {
+            if !magnet_bonus.is_finite() || magnet_bonus < 0.0 {
+                return Err(format!(
+                    "archetype '{}': magnet_bonus must be 0 or more, got {}",
+                    self.name, magnet_bonus
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(text: &str) -> Result<(), String> {
+        serde_json::from_str::<ArchetypeParams>(text).unwrap().validate()
+    }
+
+    #[test]
+    fn accepts_the_base_fields() {
+        assert_eq!(
+            validate(r#"{ "name": "brute", "hit_points": 3, "min_split_size": 20, "tentacle_count": 0, "eye_count": 2 }"#),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_split_sizes_that_never_stop_splitting() {
+        for size in ["0", "-5"] {
+            assert_eq!(
+                validate(&format!(r#"{{ "name": "brute", "min_split_size": {} }}"#, size)),
+                Err(format!("archetype 'brute': min_split_size must be positive, got {}", size))
+            );
+        }
+        // JSON has no NaN, but archetypes built in code go through the same check
+        let params = ArchetypeParams {
+            name: "brute".to_string(),
+            min_split_size: Some(f32::NAN),
+            ..ArchetypeParams::default()
+        };
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_feature_counts_that_cant_be_drawn() {
+        assert_eq!(
+            validate(r#"{ "name": "squid", "tentacle_count": 40 }"#),
+            Err("archetype 'squid': tentacle_count must be at most 12, got 40".to_string())
+        );
+        assert_eq!(
+            validate(r#"{ "name": "blind", "eye_count": 0 }"#),
+            Err("archetype 'blind': eye_count must be between 1 and 8, got 0".to_string())
+        );
+        assert!(validate(r#"{ "name": "argus", "eye_count": 100 }"#).is_err());
+        assert_eq!(
+            validate(r#"{ "name": "bulge", "eye_sizes": [0.1, 0.5] }"#),
+            Err("archetype 'bulge': eye sizes must be above 0.0 and at most 0.25, got 0.5".to_string())
+        );
+        assert!(validate(r#"{ "name": "bulge", "eye_sizes": [] }"#).is_err());
+    }
+
+    #[test]
+    fn personality_ranges_come_from_the_data() {
+        let params: ArchetypeParams = serde_json::from_str(
+            r#"{ "name": "jelly", "wobble_amplitude": [1.5, 2], "wobble_speed": [0.5, 0.5],
+                 "tentacle_noise_scale": [5, 10], "point_count": [12, 14] }"#,
+        )
+        .unwrap();
+        assert_eq!(params.validate(), Ok(()));
+        let ranges = params.build(&Archetype::default()).personality;
+        assert_eq!(ranges.wobble_amplitude, 1.5..2.0);
+        assert_eq!(ranges.wobble_speed, 0.5..0.5);
+        assert_eq!(ranges.tentacle_noise_scale, 5.0..10.0);
+        assert_eq!(ranges.point_count, 12..15);
+
+        assert_eq!(
+            validate(r#"{ "name": "jelly", "wobble_speed": [2, 1] }"#),
+            Err("archetype 'jelly': wobble_speed must be [min, max] with 0 <= min <= max, got [2, 1]".to_string())
+        );
+        assert!(validate(r#"{ "name": "jelly", "point_count": [2, 10] }"#).is_err());
+        assert!(validate(r#"{ "name": "jelly", "point_count": [10, 500] }"#).is_err());
+    }
+
+    #[test]
+    fn wandering_comes_from_the_data() {
+        let params: ArchetypeParams =
+            serde_json::from_str(r#"{ "name": "drifter", "wander_leash_radius": 120, "wander_speed": 0.4 }"#).unwrap();
+        let archetype = params.build(&Archetype::default());
+        assert_eq!((archetype.wander_leash_radius, archetype.wander_speed), (120.0, 0.4));
+        assert_eq!(
+            validate(r#"{ "name": "drifter", "wander_speed": -1 }"#),
+            Err("archetype 'drifter': wander_speed must be 0 or more, got -1".to_string())
+        );
+    }
+
+    #[test]
+    fn eye_layout_comes_from_the_data() {
+        let params: ArchetypeParams = serde_json::from_str(
+            r#"{ "name": "spider", "eye_count": 6, "eye_placement": "outline", "eye_gaze": "independent",
+                 "eye_sizes": [0.08, 0.05], "blink_rate": 0.5 }"#,
+        )
+        .unwrap();
+        assert_eq!(params.validate(), Ok(()));
+        let layout = params.build(&Archetype::default()).eye_layout;
+        assert_eq!(layout.count, 6);
+        assert_eq!(layout.placement, EyePlacement::Outline);
+        assert_eq!(layout.gaze, GazeMode::Independent);
+        assert_eq!(layout.sizes, vec![0.08, 0.05]);
+        assert_eq!(layout.blink_rate, 0.5);
+
+        let staring: ArchetypeParams = serde_json::from_str(r#"{ "name": "stare", "blink_rate": -1 }"#).unwrap();
+        assert_eq!(
+            staring.validate(),
+            Err("archetype 'stare': blink_rate must be 0 or more, got -1".to_string())
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use crate::archetype::Archetype;
+use crate::collectible::Collectible;
+use crate::eye_cluster::{EyeCluster, EyeLayout};
+use crate::personality::Personality;
+use crate::tentacle::Tentacle;
+use crate::utils::get_dynamic_color;
+use ggez::graphics::Color;
+use ggez::{Context, GameResult};
+use mint::{Point2, Vector2};
+use noise::NoiseFn;
+use serde::{Deserialize, Serialize};
+
+pub const WANDER_LEASH_RADIUS: f32 = 60.0;
+pub const WANDER_SPEED: f32 = 0.15; // how fast the wander path is walked through the noise field
+pub const WANDER_MAX_DRIFT_SPEED: f32 = 40.0; // pixels per second
+pub const TENTACLE_BASE_THICKNESS: f32 = 8.0;
+// Tip colors handed out to tentacles in turn; the base always takes the body's color
+pub const TENTACLE_TIP_COLORS: [Color; 3] = [
+    Color::new(1.0, 0.5, 0.5, 0.6),
+    Color::new(0.6, 0.8, 1.0, 0.6),
+    Color::new(1.0, 0.95, 0.6, 0.6),
+];
+
+// A piece of collectible logic that can be attached to any collectible.
+// Behaviors run in the order they were attached, so later ones see the
+// changes made by earlier ones within the same frame.
+pub trait Behavior: std::fmt::Debug {
+    fn update(
+        &mut self,
+        _ctx: &mut Context,
+        _collectible: &mut Collectible,
+        _dt: f32,
+        _player_position: Point2<f32>,
+    ) -> GameResult<()> {
+        Ok(())
+    }
+
+    // Called whenever the player is within the proximity threshold
+    fn on_proximity(&mut self, _collectible: &mut Collectible, _distance: f32) {}
+
+    // Called when the player touches the collectible
+    fn on_collision(&mut self, _collectible: &mut Collectible) {}
+
+    // Drawn on top of the collectible's body
+    fn draw(&self, _ctx: &mut Context, _collectible: &Collectible) -> GameResult<()> {
+        Ok(())
+    }
+}
+
+// The built-in behaviors, as archetypes name them in data files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BehaviorKind {
+    ColorShifting,
+    IdleWandering,
+    TentacleReaching,
+    EyeTracking,
+}
+
+// Every built-in behavior, in the order collectibles get them unless their archetype says otherwise
+pub const DEFAULT_BEHAVIORS: [BehaviorKind; 4] = [
+    BehaviorKind::ColorShifting,
+    BehaviorKind::IdleWandering,
+    BehaviorKind::TentacleReaching,
+    BehaviorKind::EyeTracking,
+];
+
+// The built-in behaviors `archetype` names, in the order it names them
+pub fn archetype_behaviors(
+    position: Point2<f32>,
+    size: f32,
+    archetype: &Archetype,
+    personality: &Personality,
+) -> Vec<Box<dyn Behavior>> {
+    archetype
+        .behaviors
+        .iter()
+        .map(|kind| -> Box<dyn Behavior> {
+            match kind {
+                BehaviorKind::ColorShifting => Box::new(ColorShifting),
+                BehaviorKind::IdleWandering => Box::new(IdleWandering::new(
+                    position,
+                    archetype.wander_leash_radius,
+                    archetype.wander_speed,
+                )),
+                BehaviorKind::TentacleReaching => {
+                    Box::new(TentacleReaching::new(position, size, archetype.tentacle_count, personality))
+                }
+                BehaviorKind::EyeTracking => {
+                    Box::new(EyeTracking::new(position, size, archetype.eye_layout.clone(), personality))
+                }
+            }
+        })
+        .collect()
+}
+
+// Shifts the body color with time and distance to the player
+#[derive(Debug)]
+pub struct ColorShifting;
+
+impl Behavior for ColorShifting {
+    fn update(
+        &mut self,
+        _ctx: &mut Context,
+        collectible: &mut Collectible,
+        _dt: f32,
+        _player_position: Point2<f32>,
+    ) -> GameResult<()> {
+        collectible.color = get_dynamic_color(
+            collectible.time,
+            collectible.normalized_distance,
+            collectible.in_proximity,
+        );
+        Ok(())
+    }
+}
+
+// Grows tentacles from around the outline towards the player while they are close.
+// They all chase the same target, but each has its own noise, phase and colors.
+#[derive(Debug)]
+pub struct TentacleReaching {
+    pub tentacles: Vec<Tentacle>,
+    // Where each tentacle is rooted, relative to the collectible's center
+    anchors: Vec<Vector2<f32>>,
+}
+
+impl TentacleReaching {
+    pub fn new(position: Point2<f32>, size: f32, count: usize, personality: &Personality) -> Self {
+        let mut tentacles = Vec::with_capacity(count);
+        let mut anchors = Vec::with_capacity(count);
+        for i in 0..count {
+            let angle = i as f32 * std::f32::consts::TAU / count as f32;
+            let anchor = Vector2 {
+                x: angle.cos() * size / 2.0 * 0.8,
+                y: angle.sin() * size / 2.0 * 0.8,
+            };
+            let mut tentacle = Tentacle::new(
+                Point2 {
+                    x: position.x + anchor.x,
+                    y: position.y + anchor.y,
+                },
+                TENTACLE_BASE_THICKNESS,
+                Color::new(1.0, 0.5, 0.5, 0.0),
+                2.5,
+                // Offset the seed so the tentacle doesn't mirror the outline's wobble
+                personality.noise_seed.wrapping_add(1 + i as u32),
+                personality.tentacle_noise_scale,
+            );
+            tentacle.tip_color = TENTACLE_TIP_COLORS[i % TENTACLE_TIP_COLORS.len()];
+            tentacle.phase = i as f32 * 1.7;
+            tentacles.push(tentacle);
+            anchors.push(anchor);
+        }
+        TentacleReaching { tentacles, anchors }
+    }
+}
+
+impl Behavior for TentacleReaching {
+    fn update(
+        &mut self,
+        ctx: &mut Context,
+        collectible: &mut Collectible,
+        dt: f32,
+        player_position: Point2<f32>,
+    ) -> GameResult<()> {
+        for (tentacle, anchor) in self.tentacles.iter_mut().zip(self.anchors.iter()) {
+            tentacle.base_position = Point2 {
+                x: collectible.position.x + anchor.x,
+                y: collectible.position.y + anchor.y,
+            };
+            tentacle.update(
+                ctx,
+                dt,
+                player_position,
+                collectible.normalized_distance,
+                collectible.time,
+                collectible.color,
+                collectible.in_proximity,
+                collectible.max_distance_threshold,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn draw(&self, ctx: &mut Context, _collectible: &Collectible) -> GameResult<()> {
+        for tentacle in &self.tentacles {
+            tentacle.draw(ctx)?;
+        }
+        Ok(())
+    }
+}
+
+// Keeps an eye (or several) on the player, blinking and darting about while it does
+#[derive(Debug)]
+pub struct EyeTracking {
+    pub eyes: EyeCluster,
+}
+
+impl EyeTracking {
+    pub fn new(position: Point2<f32>, size: f32, layout: EyeLayout, personality: &Personality) -> Self {
+        EyeTracking {
+            eyes: EyeCluster::new(position, size, layout, personality.seed),
+        }
+    }
+}
+
+impl Behavior for EyeTracking {
+    fn update(
+        &mut self,
+        _ctx: &mut Context,
+        collectible: &mut Collectible,
+        dt: f32,
+        player_position: Point2<f32>,
+    ) -> GameResult<()> {
+        self.eyes.update(
+            dt,
+            player_position,
+            collectible.position,
+            collectible.distance_from_player,
+            collectible.in_proximity,
+        );
+        Ok(())
+    }
+
+    fn draw(&self, ctx: &mut Context, _collectible: &Collectible) -> GameResult<()> {
+        self.eyes.draw(ctx)
+    }
+}
+
+// Drifts around the spawn point along a path traced through the collectible's own noise.
+// Steps aside whenever something is actively steering the collectible.
+#[derive(Debug)]
+pub struct IdleWandering {
+    pub spawn_position: Point2<f32>,
+    pub leash_radius: f32,
+    pub speed: f32,
+    time: f64,
+}
+
+impl IdleWandering {
+    pub fn new(spawn_position: Point2<f32>, leash_radius: f32, speed: f32) -> Self {
+        IdleWandering {
+            spawn_position,
+            leash_radius,
+            speed,
+            time: 0.0,
+        }
+    }
+
+    fn is_steered(collectible: &Collectible) -> bool {
+        let knockback = collectible.velocity.x.abs() + collectible.velocity.y.abs();
+        collectible.in_proximity || collectible.magnet_pull > 0.0 || knockback > 1.0
+    }
+
+    // Walks one step towards a target that never leaves the leash, so a collectible that
+    // starts inside it stays inside
+    fn wander(&mut self, collectible: &mut Collectible, dt: f32) {
+        if IdleWandering::is_steered(collectible) {
+            return;
+        }
+
+        // Start from the collectible's own time so neighbours don't drift in lockstep
+        self.time += (dt * self.speed) as f64;
+        let t = collectible.time as f64 * 0.01 + self.time;
+        let noise = collectible.noise();
+        let mut offset = Vector2 {
+            x: noise.get([t, 17.3]) as f32 * self.leash_radius,
+            y: noise.get([-41.7, t]) as f32 * self.leash_radius,
+        };
+        let length = (offset.x * offset.x + offset.y * offset.y).sqrt();
+        if length > self.leash_radius {
+            offset.x *= self.leash_radius / length;
+            offset.y *= self.leash_radius / length;
+        }
+        let target = Point2 {
+            x: self.spawn_position.x + offset.x,
+            y: self.spawn_position.y + offset.y,
+        };
+
+        let dx = target.x - collectible.position.x;
+        let dy = target.y - collectible.position.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance > 0.0 {
+            let step = (WANDER_MAX_DRIFT_SPEED * dt).min(distance);
+            collectible.position.x += dx / distance * step;
+            collectible.position.y += dy / distance * step;
+        }
+    }
+}
+
+impl Behavior for IdleWandering {
+    fn update(
+        &mut self,
+        _ctx: &mut Context,
+        collectible: &mut Collectible,
+        dt: f32,
+        _player_position: Point2<f32>,
+    ) -> GameResult<()> {
+        self.wander(collectible, dt);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::personality::PersonalityRanges;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const SPAWN: Point2<f32> = Point2 { x: 300.0, y: 200.0 };
+
+    fn collectible(seed: u64) -> Collectible {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let personality = Personality::random(&mut rng, &PersonalityRanges::default());
+        let archetype = Archetype {
+            behaviors: Vec::new(),
+            ..Archetype::default()
+        };
+        Collectible::new(SPAWN, 80.0, 1000.0, 0.0, "wanderer".to_string(), archetype, personality)
+    }
+
+    fn distance_from_spawn(collectible: &Collectible) -> f32 {
+        let dx = collectible.position.x - SPAWN.x;
+        let dy = collectible.position.y - SPAWN.y;
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    #[test]
+    fn wanderer_stays_on_its_leash() {
+        for seed in 0..5 {
+            let mut collectible = collectible(seed);
+            // Fast enough to walk a long way through the noise field
+            let mut wandering = IdleWandering::new(SPAWN, 25.0, 3.0);
+            let mut furthest: f32 = 0.0;
+            for _ in 0..2000 {
+                wandering.wander(&mut collectible, 0.05);
+                furthest = furthest.max(distance_from_spawn(&collectible));
+                assert!(distance_from_spawn(&collectible) <= 25.0 + 1e-3);
+            }
+            assert!(furthest > 5.0, "seed {} never left the spawn point", seed);
+        }
+    }
+
+    #[test]
+    fn steered_collectibles_are_left_alone() {
+        let mut collectible = collectible(1);
+        collectible.in_proximity = true;
+        let mut wandering = IdleWandering::new(SPAWN, 25.0, 3.0);
+        for _ in 0..100 {
+            wandering.wander(&mut collectible, 0.05);
+        }
+        assert_eq!(collectible.position, SPAWN);
+    }
+}
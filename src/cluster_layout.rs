@@ -0,0 +1,241 @@
+// Cluster layouts loaded from JSON files, e.g. `resources/layouts/rows.json`:
+//
+// { "name": "rows", "clusters": [ { "x": 0.8, "y": 0.5, "spread": 300, "count": 2, "weight": 1.0 } ] }
+//
+// `x`/`y` are fractions of the screen size. `spread`, `count` and `weight` are optional:
+// spread falls back to the caller's cluster size, a cluster with a `count` always gets exactly
+// that many collectibles, and the rest are shared out between the other clusters by weight.
+use ggez::{GameError, GameResult};
+use mint::Point2;
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const LAYOUTS_DIR: &str = "resources/layouts";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterSpec {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spread: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl ClusterSpec {
+    pub fn center(&self, screen_width: f32, screen_height: f32) -> Point2<f32> {
+        Point2 {
+            x: self.x * screen_width,
+            y: self.y * screen_height,
+        }
+    }
+
+    pub fn spread_or(&self, default_spread: f32) -> f32 {
+        self.spread.unwrap_or(default_spread)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterLayout {
+    pub name: String,
+    pub clusters: Vec<ClusterSpec>,
+}
+
+impl ClusterLayout {
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<ClusterLayout> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            GameError::CustomError(format!("Can't read cluster layout {}: {}", path.display(), e))
+        })?;
+        ClusterLayout::parse(&text, &path.display().to_string())
+    }
+
+    // Every `*.json` file in `dir`, sorted by file name
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> GameResult<Vec<ClusterLayout>> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            GameError::CustomError(format!("Can't read layout directory {}: {}", dir.display(), e))
+        })?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        if paths.is_empty() {
+            return Err(GameError::CustomError(format!(
+                "No cluster layouts (*.json) found in {}",
+                dir.display()
+            )));
+        }
+        paths.iter().map(ClusterLayout::load).collect()
+    }
+
+    // `source` names where the text came from in error messages
+    pub fn parse(text: &str, source: &str) -> GameResult<ClusterLayout> {
+        let layout: ClusterLayout = serde_json::from_str(text).map_err(|e| {
+            GameError::CustomError(format!("Invalid cluster layout {}: {}", source, e))
+        })?;
+        layout
+            .validate()
+            .map_err(|e| GameError::CustomError(format!("Invalid cluster layout {}: {}", source, e)))?;
+        Ok(layout)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if self.clusters.is_empty() {
+            return Err(format!("layout '{}' has no clusters", self.name));
+        }
+        for (i, cluster) in self.clusters.iter().enumerate() {
+            if !(0.0..=1.0).contains(&cluster.x) || !(0.0..=1.0).contains(&cluster.y) {
+                return Err(format!(
+                    "cluster {}: x and y are screen fractions and must be between 0.0 and 1.0, got ({}, {})",
+                    i, cluster.x, cluster.y
+                ));
+            }
+            if let Some(spread) = cluster.spread {
+                if !spread.is_finite() || spread < 0.0 {
+                    return Err(format!("cluster {}: spread must be 0 or more, got {}", i, spread));
+                }
+            }
+            if !cluster.weight.is_finite() || cluster.weight < 0.0 {
+                return Err(format!("cluster {}: weight must be 0 or more, got {}", i, cluster.weight));
+            }
+        }
+        let shared: Vec<&ClusterSpec> = self.clusters.iter().filter(|c| c.count.is_none()).collect();
+        if !shared.is_empty() && shared.iter().all(|c| c.weight == 0.0) {
+            return Err("every cluster without a fixed count has weight 0, so none of them can be used".to_string());
+        }
+        Ok(())
+    }
+
+    // How many of `total` collectibles go to each cluster. Fixed counts come first, in file
+    // order and as far as `total` allows; the rest are drawn one at a time by weight. The
+    // quotas add up to less than `total` only if every cluster has a fixed count.
+    pub fn quotas<R: Rng + ?Sized>(&self, total: usize, rng: &mut R) -> Vec<usize> {
+        let mut quotas = vec![0; self.clusters.len()];
+        let mut remaining = total;
+        for (quota, cluster) in quotas.iter_mut().zip(&self.clusters) {
+            if let Some(count) = cluster.count {
+                *quota = (count as usize).min(remaining);
+                remaining -= *quota;
+            }
+        }
+
+        let weights: Vec<f32> = self
+            .clusters
+            .iter()
+            .map(|c| if c.count.is_none() { c.weight } else { 0.0 })
+            .collect();
+        if let Ok(distribution) = WeightedIndex::new(&weights) {
+            for _ in 0..remaining {
+                quotas[distribution.sample(rng)] += 1;
+            }
+        }
+        quotas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn parse_error(text: &str) -> String {
+        match ClusterLayout::parse(text, "test.json") {
+            Err(GameError::CustomError(message)) => message,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_a_layout() {
+        let layout = ClusterLayout::parse(
+            r#"{ "name": "rows", "clusters": [ { "x": 0.8, "y": 0.5, "spread": 300, "count": 2 } ] }"#,
+            "test.json",
+        )
+        .unwrap();
+        assert_eq!(layout.name, "rows");
+        assert_eq!(layout.clusters[0].count, Some(2));
+        assert_eq!(layout.clusters[0].weight, 1.0);
+        assert_eq!(layout.clusters[0].center(1000.0, 800.0), Point2 { x: 800.0, y: 400.0 });
+    }
+
+    #[test]
+    fn rejects_a_layout_without_clusters() {
+        assert_eq!(
+            parse_error(r#"{ "name": "empty", "clusters": [] }"#),
+            "Invalid cluster layout test.json: layout 'empty' has no clusters"
+        );
+    }
+
+    #[test]
+    fn rejects_clusters_off_screen() {
+        assert_eq!(
+            parse_error(r#"{ "name": "wide", "clusters": [ { "x": 0.5, "y": 0.5 }, { "x": 1.5, "y": 0.5 } ] }"#),
+            "Invalid cluster layout test.json: cluster 1: x and y are screen fractions and must be between 0.0 and 1.0, got (1.5, 0.5)"
+        );
+        assert!(parse_error(r#"{ "name": "high", "clusters": [ { "x": 0.5, "y": -0.1 } ] }"#).contains("cluster 0: x and y"));
+    }
+
+    #[test]
+    fn rejects_weights_that_leave_nothing_to_share() {
+        assert_eq!(
+            parse_error(r#"{ "name": "odd", "clusters": [ { "x": 0.5, "y": 0.5, "weight": -1 } ] }"#),
+            "Invalid cluster layout test.json: cluster 0: weight must be 0 or more, got -1"
+        );
+        assert!(parse_error(r#"{ "name": "zero", "clusters": [ { "x": 0.5, "y": 0.5, "weight": 0 } ] }"#)
+            .contains("every cluster without a fixed count has weight 0"));
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(parse_error(r#"{ "name": "typo", "clusters": [ { "x": 0.5, "y": 0.5, "sprad": 3 } ] }"#)
+            .contains("unknown field `sprad`"));
+    }
+
+    #[test]
+    fn fixed_counts_come_first_and_weights_share_the_rest() {
+        let layout = ClusterLayout::parse(
+            r#"{ "name": "split", "clusters": [
+                { "x": 0.1, "y": 0.5, "count": 3 },
+                { "x": 0.5, "y": 0.5, "weight": 1 },
+                { "x": 0.9, "y": 0.5, "weight": 0 }
+            ] }"#,
+            "test.json",
+        )
+        .unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        assert_eq!(layout.quotas(10, &mut rng), vec![3, 7, 0]);
+        // A fixed count never takes more than there is
+        assert_eq!(layout.quotas(2, &mut rng), vec![2, 0, 0]);
+    }
+
+    #[test]
+    fn quotas_add_up_to_the_total() {
+        let layout = ClusterLayout::parse(
+            r#"{ "name": "even", "clusters": [ { "x": 0.2, "y": 0.5 }, { "x": 0.8, "y": 0.5, "weight": 3 } ] }"#,
+            "test.json",
+        )
+        .unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let quotas = layout.quotas(400, &mut rng);
+        assert_eq!(quotas.iter().sum::<usize>(), 400);
+        // Three times the weight draws roughly three times as many
+        assert!(quotas[1] > quotas[0] * 2);
+    }
+}
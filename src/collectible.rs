@@ -1,17 +1,35 @@
 use std::f32::INFINITY;
 
-use crate::amorphous_mesh_creator;
+use crate::amorphous_mesh_creator::{self, Distortion};
+use crate::archetype::Archetype;
+use crate::behavior::{self, Behavior};
 use crate::collidable::Collidable;
-use crate::eye::Eye;
-use crate::heart_mesh;
-use crate::smoke_effect::SmokeEffect;
-use crate::tentacle::Tentacle;
-use crate::utils::get_dynamic_color;
-use ggez::graphics::{self, Color, Mesh, Rect};
+use crate::heart_outline;
+use crate::effects::Effects;
+use crate::metaball::MetaBlob;
+use crate::personality::Personality;
+use crate::soft_body::SoftBody;
+use ggez::graphics::{self, Color, Rect};
+use easer::functions::{Cubic, Easing, Quad};
 use ggez::{Context, GameResult};
 use mint::{Point2, Vector2};
 
-use noise::Perlin;
+use noise::{Perlin, Seedable};
+use rand::Rng;
+
+pub const MAGNET_RAMP_TIME: f32 = 0.6; // seconds to reach full pull speed
+pub const MAGNET_MAX_SPEED: f32 = 900.0;
+pub const MAGNET_MAX_STRETCH: f32 = 0.6;
+pub const HIT_FLASH_TIME: f32 = 0.15;
+pub const HIT_SQUASH_AMOUNT: f32 = 0.8;
+pub const HIT_SQUASH_RECOVERY: f32 = 4.0; // squash units recovered per second
+pub const HIT_KNOCKBACK_SPEED: f32 = 700.0;
+pub const KNOCKBACK_DAMPING: f32 = 6.0;
+pub const HIT_INVULNERABILITY_TIME: f32 = 0.4;
+pub const SPLIT_SPEED: f32 = 400.0;
+// The blob starts turning into a heart at the first distance and is fully a heart by the second
+pub const HEART_MORPH_START_DISTANCE: f32 = 300.0;
+pub const HEART_MORPH_END_DISTANCE: f32 = 150.0;
 #[derive(Debug)]
 pub struct Collectible {
     pub position: Point2<f32>,
@@ -23,45 +41,58 @@ pub struct Collectible {
     pub in_proximity: bool,
     pub distance_from_player: f32,
     pub normalized_distance: f32,
-    mesh: Mesh,
-    pub heart_mesh: Mesh,
+    // Outline around the origin as of the last update; the mesh is built from it when drawn
+    outline: Vec<Point2<f32>>,
+    // Heart outline with the same point count and center as the blob, to morph into
+    heart_points: Vec<Point2<f32>>,
     noise: Perlin,
     pub player_direction: mint::Vector2<f32>,
-    pub eye: Eye,
-    pub tentacle: Tentacle,
+    behaviors: Vec<Box<dyn Behavior>>,
     pub color: Color,
     pub max_distance_threshold: f32,
+    pub magnet_pull: f32,
+    pub magnet_direction: Vector2<f32>,
+    pub health: Option<u32>,
+    pub velocity: Vector2<f32>,
+    flash_timer: f32,
+    squash: f32,
+    hit_cooldown: f32,
+    pub archetype: Archetype,
+    // Index of the cluster this collectible was placed around, if any
+    pub cluster: Option<usize>,
+    pub personality: Personality,
+    pub soft_body: SoftBody,
 }
 
 impl Collectible {
+    // Starts with the built-in behaviors its archetype names
     pub fn new(
-        ctx: &mut Context,
-        x: f32,
-        y: f32,
+        position: Point2<f32>,
         size: f32,
         max_distance_threshold: f32,
         initial_time: f32,
         id: String,
-        in_proximity: bool,
-        distance_from_player: f32,
-    ) -> GameResult<Self> {
-        let eye = Eye::new(x, y, size / 10.0);
-        let heart_mesh = heart_mesh::create_heart_mesh(ctx, size/2.0, Color::new(1.0, 0.0, 0.0, 1.0))?;
+        archetype: Archetype,
+        personality: Personality,
+    ) -> Self {
+        let heart_points = heart_outline::heart_outline(size / 2.0, personality.point_count);
 
-        let noise = Perlin::new();
+        let noise = Perlin::new().set_seed(personality.noise_seed);
         let color: Color = Color::new(1.0, 0.0, 0.0, 1.0);
         let normalized_distance_from_player = 0.01;
-        let mesh = amorphous_mesh_creator::create_amorphous_mesh(
-            ctx,
+        let outline = amorphous_mesh_creator::amorphous_points(
             size,
             &noise,
             initial_time,
-            in_proximity,
+            false,
             normalized_distance_from_player,
-        )?;
+            &personality,
+            &Distortion::none(),
+        );
+        let behaviors = behavior::archetype_behaviors(position, size, &archetype, &personality);
         // println!("im alive! {}", id);
-        Ok(Collectible {
-            position: Point2 { x, y },
+        let mut collectible = Collectible {
+            position,
             size,
             active: true,
             radius: size / 2.0,
@@ -70,16 +101,29 @@ impl Collectible {
             in_proximity: false,
             distance_from_player: 10000.0,
             normalized_distance: normalized_distance_from_player,
-            mesh,
-            heart_mesh,
+            outline,
+            heart_points,
             noise,
             player_direction: Vector2 { x: 0.0, y: 0.0 },
-            eye,
-            tentacle: Tentacle::new(Point2 { x, y }, 5.0, Color::new(1.0, 0.5, 0.5, 0.0), 2.5),
+            behaviors: Vec::new(),
             color,
             max_distance_threshold,
-        })
-       
+            magnet_pull: 0.0,
+            magnet_direction: Vector2 { x: 0.0, y: 0.0 },
+            health: archetype.hit_points,
+            velocity: Vector2 { x: 0.0, y: 0.0 },
+            flash_timer: 0.0,
+            squash: 0.0,
+            hit_cooldown: 0.0,
+            archetype,
+            cluster: None,
+            personality,
+            soft_body: SoftBody::new(personality.point_count),
+        };
+        for behavior in behaviors {
+            collectible.add_behavior(behavior);
+        }
+        collectible
     }
 
     pub fn update_distance(&mut self, distance: f32, max_distance_threshold: f32) {
@@ -97,8 +141,9 @@ impl Collectible {
         &mut self,
         ctx: &mut Context,
         dt: f32,
-        player_position: mint::Point2<f32>
-       
+        player_position: mint::Point2<f32>,
+        player_radius: f32,
+        magnet_radius: f32,
     ) -> GameResult<()> {
         pub fn calculate_distance(point1: Point2<f32>, point2: Point2<f32>) -> f32 {
             let dx = point2.x - point1.x;
@@ -106,68 +151,257 @@ impl Collectible {
             f32::sqrt(dx * dx + dy * dy)
         }
         self.time += dt;
+        self.update_hit_reaction(dt);
+        self.apply_magnet(dt, player_position, magnet_radius);
+        self.apply_contact(dt, player_position, player_radius);
         self.distance_from_player = calculate_distance(self.position , player_position);
         // println!("distance {}", self.distance_from_player);
         let clamped_distance = self.distance_from_player.clamp(2.0, self.max_distance_threshold);
         self.normalized_distance =
             1.0 - (clamped_distance - 50.0) / (self.max_distance_threshold - 50.0);
-        self.mesh = amorphous_mesh_creator::create_amorphous_mesh(
-            ctx,
+        let blob_points = amorphous_mesh_creator::amorphous_points(
             self.size,
             &self.noise,
             self.time,
             self.in_proximity,
             self.normalized_distance,
-        )?;
+            &self.personality,
+            &Distortion {
+                stretch_direction: self.magnet_direction,
+                stretch_amount: Quad::ease_out(self.magnet_pull, 0.0, MAGNET_MAX_STRETCH, 1.0),
+                squash: self.squash,
+                deformation: &self.soft_body.offsets,
+            },
+        );
+        self.outline = amorphous_mesh_creator::morph_points(&blob_points, &self.heart_points, self.heart_morph());
 
-        self.color = get_dynamic_color(self.time, self.normalized_distance, self.in_proximity);
+        // Stop at the first behavior that fails; the rest get their turn next frame
+        let mut result = Ok(());
+        self.for_each_behavior(|behavior, collectible| {
+            if result.is_ok() {
+                result = behavior.update(ctx, collectible, dt, player_position);
+            }
+        });
+        result
+    }
 
-        self.tentacle.update(
-            ctx,
-            player_position,
-            250.0,
-            self.normalized_distance,
-            self.time,
-            self.color,
-            self.in_proximity,
-            self.max_distance_threshold,
-        )?;
+    // Applies a hit coming from `source` (the player or a projectile).
+    // Returns true when this was the final hit and the collectible should be collected.
+    pub fn take_hit(&mut self, source: Point2<f32>, damage: u32) -> bool {
+        if self.hit_cooldown > 0.0 {
+            return false;
+        }
 
-         self.eye.update(player_position, self.position, self.distance_from_player, self.in_proximity);
+        let health = match self.health {
+            Some(health) => health.saturating_sub(damage),
+            None => return true,
+        };
+        self.health = Some(health);
+        if health == 0 {
+            return true;
+        }
 
-        Ok(())
+        self.flash_timer = HIT_FLASH_TIME;
+        self.squash = HIT_SQUASH_AMOUNT;
+        self.hit_cooldown = HIT_INVULNERABILITY_TIME;
+        self.magnet_pull = 0.0;
+
+        let away = Vector2 {
+            x: self.position.x - source.x,
+            y: self.position.y - source.y,
+        };
+        let distance = (away.x.powi(2) + away.y.powi(2)).sqrt();
+        if distance > 0.0 {
+            self.velocity.x += away.x / distance * HIT_KNOCKBACK_SPEED;
+            self.velocity.y += away.y / distance * HIT_KNOCKBACK_SPEED;
+        }
+        false
+    }
+
+    pub fn can_split(&self) -> bool {
+        self.archetype.can_split(self.size)
+    }
+
+    // Breaks the collectible into smaller copies of itself flying outwards.
+    // Children keep the parent's archetype and cluster, and get ids derived from the parent's.
+    pub fn split<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<Collectible> {
+        let min_count = self.archetype.split_count_min.min(self.archetype.split_count_max);
+        let count = rng.gen_range(min_count..=self.archetype.split_count_max);
+        let child_size = self.size * self.archetype.split_scale;
+        let base_angle = rng.gen_range(0.0..std::f32::consts::TAU);
+
+        let mut children = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let angle = base_angle + i as f32 * std::f32::consts::TAU / count as f32;
+            let direction = Vector2 { x: angle.cos(), y: angle.sin() };
+
+            let mut child = Collectible::new(
+                Point2 {
+                    x: self.position.x + direction.x * child_size / 2.0,
+                    y: self.position.y + direction.y * child_size / 2.0,
+                },
+                child_size,
+                self.max_distance_threshold,
+                rng.gen_range(0.0..std::f32::consts::TAU),
+                format!("{}.{}", self.id, i),
+                self.archetype.clone(),
+                // A fresh seed so children don't wobble in step with each other
+                Personality::random(rng, &self.archetype.personality),
+            );
+            child.cluster = self.cluster;
+            child.velocity = Vector2 {
+                x: direction.x * SPLIT_SPEED,
+                y: direction.y * SPLIT_SPEED,
+            };
+            child.hit_cooldown = HIT_INVULNERABILITY_TIME;
+            children.push(child);
+        }
+        children
+    }
+
+    // Winds down the flash, squash and knockback left over from the last hit
+    fn update_hit_reaction(&mut self, dt: f32) {
+        self.flash_timer = (self.flash_timer - dt).max(0.0);
+        self.hit_cooldown = (self.hit_cooldown - dt).max(0.0);
+        self.squash = (self.squash - HIT_SQUASH_RECOVERY * dt).max(0.0);
+
+        self.position.x += self.velocity.x * dt;
+        self.position.y += self.velocity.y * dt;
+        let damping = (1.0 - KNOCKBACK_DAMPING * dt).max(0.0);
+        self.velocity.x *= damping;
+        self.velocity.y *= damping;
+    }
+
+    // Body color with the hit flash blended in
+    pub fn body_color(&self) -> Color {
+        let flash = self.flash_timer / HIT_FLASH_TIME;
+        Color::new(
+            self.color.r + (1.0 - self.color.r) * flash,
+            self.color.g + (1.0 - self.color.g) * flash,
+            self.color.b + (1.0 - self.color.b) * flash,
+            self.color.a,
+        )
+    }
+
+    // The noise that drives this collectible's outline, shared with behaviors that want
+    // motion that matches its wobble
+    pub fn noise(&self) -> &Perlin {
+        &self.noise
+    }
+
+    // Attaches a behavior after the ones already there, e.g. one a new creature type
+    // brings along. Behaviors may attach others while they run.
+    pub fn add_behavior(&mut self, behavior: Box<dyn Behavior>) {
+        self.behaviors.push(behavior);
+    }
+
+    pub fn on_proximity(&mut self, distance: f32) {
+        self.for_each_behavior(|behavior, collectible| behavior.on_proximity(collectible, distance));
+    }
+
+    pub fn on_collision(&mut self) {
+        self.for_each_behavior(|behavior, collectible| behavior.on_collision(collectible));
+    }
+
+    // Behaviors need mutable access to the collectible that owns them, so they are
+    // moved out for the duration of the call and put back afterwards.
+    fn for_each_behavior<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Box<dyn Behavior>, &mut Collectible),
+    {
+        let mut behaviors = std::mem::take(&mut self.behaviors);
+        for behavior in behaviors.iter_mut() {
+            f(behavior, self);
+        }
+        // Keep anything a behavior attached while it was running
+        behaviors.append(&mut self.behaviors);
+        self.behaviors = behaviors;
     }
+
+    // Drags the collectible towards the player while it is inside the magnet radius.
+    // The pull ramps up with an ease-in so blobs start sluggish and then snap in.
+    fn apply_magnet(&mut self, dt: f32, player_position: Point2<f32>, magnet_radius: f32) {
+        let to_player = Vector2 {
+            x: player_position.x - self.position.x,
+            y: player_position.y - self.position.y,
+        };
+        let distance = (to_player.x.powi(2) + to_player.y.powi(2)).sqrt();
+
+        if distance < magnet_radius {
+            self.magnet_pull = (self.magnet_pull + dt / MAGNET_RAMP_TIME).min(1.0);
+        } else {
+            self.magnet_pull = (self.magnet_pull - dt / MAGNET_RAMP_TIME).max(0.0);
+        }
+
+        if self.magnet_pull <= 0.0 || distance == 0.0 {
+            return;
+        }
+
+        self.magnet_direction = Vector2 {
+            x: to_player.x / distance,
+            y: to_player.y / distance,
+        };
+        let speed = Cubic::ease_in(self.magnet_pull, 0.0, MAGNET_MAX_SPEED, 1.0);
+        let step = (speed * dt).min(distance);
+        self.position.x += self.magnet_direction.x * step;
+        self.position.y += self.magnet_direction.y * step;
+    }
+
+    // Dents the outline where the player is pressing into it and lets it spring back
+    fn apply_contact(&mut self, dt: f32, player_position: Point2<f32>, player_radius: f32) {
+        let dx = player_position.x - self.position.x;
+        let dy = player_position.y - self.position.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let penetration = self.radius + player_radius - distance;
+        if penetration > 0.0 {
+            self.soft_body.press(dy.atan2(dx), penetration.min(self.radius));
+        }
+        self.soft_body.update(dt);
+    }
+
     pub fn draw(&self, ctx: &mut Context, player_position: mint::Point2<f32>) -> GameResult<()> {
         
         if self.active {
-       
-            if self.distance_from_player < 200.0 {
-                graphics::draw(
-                    ctx,
-                    &self.heart_mesh,
-                    graphics::DrawParam::default()
-                        .dest([self.position.x, self.position.y])
-                        .scale([self.size / self.size, self.size / self.size])
-                        .color(self.color),
-                )?;
-            } else {
-                println!("draw {}", self.id);
-                graphics::draw(
-                    ctx,
-                    &self.mesh,
-                    graphics::DrawParam::default()
-                        .dest([self.position.x, self.position.y])
-                        .scale([self.size / self.size, self.size / self.size])
-                        .color(self.color),
-                )?;
-            }
+            self.draw_body(ctx)?;
+            self.draw_features(ctx)?;
+        }
+        Ok(())
+    }
+
+    pub fn draw_body(&self, ctx: &mut Context) -> GameResult<()> {
+        let mesh = amorphous_mesh_creator::build_outline_mesh(ctx, &self.outline)?;
+        graphics::draw(
+            ctx,
+            &mesh,
+            graphics::DrawParam::default()
+                .dest([self.position.x, self.position.y])
+                .scale([self.size / self.size, self.size / self.size])
+                .color(self.body_color()),
+        )
+    }
+
+    // How far the outline has turned into a heart, eased so it settles gently at both ends
+    pub fn heart_morph(&self) -> f32 {
+        let t = (HEART_MORPH_START_DISTANCE - self.distance_from_player)
+            / (HEART_MORPH_START_DISTANCE - HEART_MORPH_END_DISTANCE);
+        Cubic::ease_in_out(t.clamp(0.0, 1.0), 0.0, 1.0, 1.0)
+    }
 
-             self.eye.draw(ctx)?;
-            self.tentacle.draw(ctx)?;
+    // Everything the behaviors draw on top of the body (eyes, tentacles, ...)
+    pub fn draw_features(&self, ctx: &mut Context) -> GameResult<()> {
+        for behavior in &self.behaviors {
+            behavior.draw(ctx, self)?;
         }
         Ok(())
     }
 
+    pub fn metablob(&self) -> MetaBlob {
+        MetaBlob {
+            center: self.position,
+            radius: self.radius,
+        }
+    }
+
     pub fn bounding_box(&self) -> Rect {
         Rect::new(
             self.position.x - self.size / 2.0,
@@ -177,16 +411,8 @@ impl Collectible {
         )
     }
 
-    pub fn activate_smoke_effect(&self, smoke_effect_pool: &mut Vec<SmokeEffect>) {
-        let base_position = Point2 {
-            x: self.position.x + self.size / 2.0,
-            y: self.position.y + self.size / 2.0,
-        };
-        for _ in 0..5 {
-            if let Some(inactive_effect) = smoke_effect_pool.iter_mut().find(|e| !e.is_active()) {
-                inactive_effect.activate(base_position);
-            }
-        }
+    pub fn activate_smoke_effect(&self, effects: &mut Effects) {
+        effects.burst(self.position, self.color);
     }
 
     pub fn set_in_proximity(&mut self, in_proximity: bool, distance: f32, max: f32) {
@@ -201,3 +427,201 @@ impl Collidable for Collectible {
         self.bounding_box()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archetype::ArchetypeParams;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn collectible(archetype: Archetype) -> Collectible {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let personality = Personality::random(&mut rng, &archetype.personality);
+        Collectible::new(Point2 { x: 100.0, y: 100.0 }, 80.0, 1000.0, 0.0, "test".to_string(), archetype, personality)
+    }
+
+    fn bare() -> Archetype {
+        Archetype {
+            behaviors: Vec::new(),
+            ..Archetype::default()
+        }
+    }
+
+    // Writes what it sees into a log the test keeps hold of
+    #[derive(Debug)]
+    struct Recorder {
+        name: &'static str,
+        log: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Behavior for Recorder {
+        fn on_proximity(&mut self, collectible: &mut Collectible, distance: f32) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} saw size {} at {}", self.name, collectible.size, distance));
+        }
+
+        fn on_collision(&mut self, collectible: &mut Collectible) {
+            self.log.borrow_mut().push(format!("{} touched {}", self.name, collectible.id));
+        }
+    }
+
+    // Shrinks the collectible whenever the player comes close
+    #[derive(Debug)]
+    struct Shy;
+
+    impl Behavior for Shy {
+        fn on_proximity(&mut self, collectible: &mut Collectible, _distance: f32) {
+            collectible.size /= 2.0;
+        }
+    }
+
+    // Brings a recorder along the first time it's touched
+    #[derive(Debug)]
+    struct Summoner {
+        log: Rc<RefCell<Vec<String>>>,
+        summoned: bool,
+    }
+
+    impl Behavior for Summoner {
+        fn on_collision(&mut self, collectible: &mut Collectible) {
+            if !self.summoned {
+                self.summoned = true;
+                collectible.add_behavior(Box::new(Recorder {
+                    name: "summoned",
+                    log: self.log.clone(),
+                }));
+            }
+        }
+    }
+
+    #[test]
+    fn custom_behaviors_run_in_the_order_they_were_attached() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut collectible = collectible(bare());
+        collectible.add_behavior(Box::new(Recorder { name: "first", log: log.clone() }));
+        collectible.add_behavior(Box::new(Shy));
+        collectible.add_behavior(Box::new(Recorder { name: "last", log: log.clone() }));
+
+        collectible.on_proximity(50.0);
+        // The last recorder sees what the behavior before it did
+        assert_eq!(*log.borrow(), vec!["first saw size 80 at 50", "last saw size 40 at 50"]);
+        assert_eq!(collectible.size, 40.0);
+    }
+
+    #[test]
+    fn behaviors_attached_while_running_are_kept() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut collectible = collectible(bare());
+        collectible.add_behavior(Box::new(Summoner { log: log.clone(), summoned: false }));
+
+        collectible.on_collision();
+        assert!(log.borrow().is_empty());
+        assert_eq!(collectible.behaviors.len(), 2);
+
+        collectible.on_collision();
+        assert_eq!(*log.borrow(), vec!["summoned touched test"]);
+        assert_eq!(collectible.behaviors.len(), 2);
+    }
+
+    #[test]
+    fn hits_wear_health_down_until_the_last_one_collects() {
+        let mut collectible = collectible(Archetype {
+            hit_points: Some(3),
+            ..bare()
+        });
+        let player = Point2 { x: 60.0, y: 100.0 };
+
+        assert!(!collectible.take_hit(player, 1));
+        assert_eq!(collectible.health, Some(2));
+        // Knocked away from whoever hit it
+        assert!(collectible.velocity.x > 0.0);
+
+        // Hits landing during the invulnerability window are ignored
+        assert!(!collectible.take_hit(player, 1));
+        assert_eq!(collectible.health, Some(2));
+
+        collectible.update_hit_reaction(HIT_INVULNERABILITY_TIME);
+        assert!(!collectible.take_hit(player, 1));
+        assert_eq!(collectible.health, Some(1));
+
+        collectible.update_hit_reaction(HIT_INVULNERABILITY_TIME);
+        assert!(collectible.take_hit(player, 5));
+        assert_eq!(collectible.health, Some(0));
+    }
+
+    #[test]
+    fn collectibles_without_hit_points_go_on_first_touch() {
+        let mut collectible = collectible(bare());
+        assert!(collectible.take_hit(Point2 { x: 0.0, y: 0.0 }, 1));
+    }
+
+    #[test]
+    fn splits_into_smaller_copies_of_itself() {
+        let archetype = Archetype {
+            split_count_min: 2,
+            split_count_max: 4,
+            split_scale: 0.5,
+            min_split_size: 15.0,
+            ..bare()
+        };
+        let parent = collectible(archetype);
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let mut counts = Vec::new();
+        for _ in 0..50 {
+            let children = parent.split(&mut rng);
+            assert!((2..=4).contains(&children.len()));
+            counts.push(children.len());
+            for (i, child) in children.iter().enumerate() {
+                assert_eq!(child.size, 40.0);
+                assert_eq!(child.archetype, parent.archetype);
+                assert_eq!(child.id, format!("test.{}", i));
+            }
+        }
+        assert!(counts.contains(&2) && counts.contains(&4));
+    }
+
+    #[test]
+    fn splitting_stops_at_the_minimum_size() {
+        let archetype = Archetype {
+            split_count_min: 2,
+            split_count_max: 2,
+            split_scale: 0.5,
+            min_split_size: 15.0,
+            ..bare()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        // 80 splits into 40s, those into 20s, and 20s would make 10s, below the minimum
+        let mut generation = vec![collectible(archetype)];
+        let mut sizes = Vec::new();
+        while generation.iter().all(|collectible| collectible.can_split()) {
+            generation = generation.iter().flat_map(|collectible| collectible.split(&mut rng)).collect();
+            sizes.push(generation[0].size);
+        }
+        assert_eq!(sizes, vec![40.0, 20.0]);
+        assert_eq!(generation.len(), 4);
+    }
+
+    #[test]
+    fn archetypes_name_their_built_in_behaviors() {
+        let names = |collectible: &Collectible| -> Vec<String> {
+            collectible
+                .behaviors
+                .iter()
+                .map(|behavior| format!("{:?}", behavior).split([' ', '{']).next().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(
+            names(&collectible(Archetype::default())),
+            vec!["ColorShifting", "IdleWandering", "TentacleReaching", "EyeTracking"]
+        );
+
+        let params: ArchetypeParams =
+            serde_json::from_str(r#"{ "name": "stare", "behaviors": ["eye_tracking", "color_shifting"] }"#).unwrap();
+        let stare = collectible(params.build(&Archetype::default()));
+        assert_eq!(names(&stare), vec!["EyeTracking", "ColorShifting"]);
+    }
+}
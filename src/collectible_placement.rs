@@ -1,89 +1,544 @@
+use crate::archetype::Archetype;
 use crate::collectible::Collectible;
-use crate::collectible_cluster_points::{self, ClusterPointCollection};
-use ggez::Context;
+use crate::density_map::{self, DensityMapParams};
+use crate::cluster_layout::ClusterLayout;
+use crate::personality::Personality;
+use crate::placement_constraints::PlacementConstraints;
+use crate::poisson_disk::{self, Region, DEFAULT_ATTEMPTS};
+use crate::world_seed::{self, WorldSeed};
+use mint::Point2;
 use rand::Rng;
 
+pub const PLACEMENT_MARGIN: f32 = 20.0; // keep collectibles this far inside the screen edges
+pub const MIN_SPACING_FACTOR: f32 = 1.2; // minimum distance between centers, in collectible sizes
+// Tried in order when not everything fits: first grow the clusters, then let the
+// collectibles sit closer together, and finally settle for placing fewer
+pub const WIDEN_STEPS: [f32; 3] = [1.0, 1.5, 2.0];
+pub const SPACING_STEPS: [f32; 3] = [1.0, 0.85, 0.7];
+// Fixed and generated collectibles share a field, so their ids are numbered apart
+pub const PLACED_ID_PREFIX: &str = "placed";
+pub const GENERATED_ID_PREFIX: &str = "collect";
+
+// A collectible at an exact position, e.g. from a level file. Without an archetype
+// the kind is picked at random.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedPlacement {
+    pub position: Point2<f32>,
+    pub archetype: Option<Archetype>,
+}
+
+// The field being filled: its size, how many collectibles go in and how big they are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldSettings {
+    pub screen_width: f32,
+    pub screen_height: f32,
+    pub collectible_count: i32,
+    // How far around a cluster's center its collectibles scatter, unless the cluster says otherwise
+    pub cluster_size: f32,
+    pub proximity_threshold: f32,
+    pub collectible_size: f32,
+    // The same seed always fills the field the same way
+    pub seed: WorldSeed,
+}
+
+// Where one collectible goes, and what it is if that's already decided
+struct Spawn<'a> {
+    position: Point2<f32>,
+    cluster: Option<usize>,
+    archetype: Option<&'a Archetype>,
+}
+
+impl Spawn<'_> {
+    fn generated((position, cluster): (Point2<f32>, Option<usize>)) -> Self {
+        Spawn {
+            position,
+            cluster,
+            archetype: None,
+        }
+    }
+}
+
+// Everything random about one collectible, decided before any of them is built.
+// Planning needs no graphics context, so a seed's layout can be checked on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectiblePlan {
+    pub position: Point2<f32>,
+    pub cluster: Option<usize>,
+    // Where in its shapeshifting cycle the collectible starts
+    pub initial_time: f32,
+    pub archetype: Archetype,
+    pub personality: Personality,
+}
+
+// One way placement was loosened to fit more collectibles in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Relaxation {
+    // Clusters spread wider (or, on a density map, the threshold lowered) by this factor
+    Widened { factor: f32 },
+    // Minimum distance between centers lowered to this many pixels
+    ReducedSpacing { spacing: f32 },
+    // Gave up on fitting everything in
+    PlacedFewer { placed: usize, requested: usize },
+}
+
+// What placement had to do to produce the field, so callers can decide whether it's good enough
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlacementReport {
+    pub requested: usize,
+    pub placed: usize,
+    // Minimum spacing the final placement used
+    pub spacing: f32,
+    pub relaxations: Vec<Relaxation>,
+}
+
+impl PlacementReport {
+    pub fn is_complete(&self) -> bool {
+        self.placed >= self.requested
+    }
+
+    pub fn was_relaxed(&self) -> bool {
+        !self.relaxations.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "placed {} of {} collectibles {:.0}px apart",
+            self.placed, self.requested, self.spacing
+        );
+        for relaxation in &self.relaxations {
+            match relaxation {
+                Relaxation::Widened { factor } => text.push_str(&format!(", widened clusters x{}", factor)),
+                Relaxation::ReducedSpacing { spacing } => {
+                    text.push_str(&format!(", reduced spacing to {:.0}px", spacing))
+                }
+                Relaxation::PlacedFewer { placed, requested } => {
+                    text.push_str(&format!(", {} did not fit", requested - placed))
+                }
+            }
+        }
+        text
+    }
+}
+
+// Centers to place collectibles at, each with the cluster it belongs to, if any
+type Positions = Vec<(Point2<f32>, Option<usize>)>;
+
+// Runs `attempt(widen, spacing)` with progressively looser settings until it returns
+// `requested` positions, and reports which steps were needed. If even the loosest
+// settings fall short, the attempt that placed the most is kept.
+fn place_with_relaxation<F>(
+    requested: usize,
+    base_spacing: f32,
+    mut attempt: F,
+) -> (Positions, PlacementReport)
+where
+    F: FnMut(f32, f32) -> Positions,
+{
+    let widest = WIDEN_STEPS[WIDEN_STEPS.len() - 1];
+    let steps = WIDEN_STEPS
+        .iter()
+        .map(|&widen| (widen, 1.0))
+        .chain(SPACING_STEPS.iter().skip(1).map(|&spacing| (widest, spacing)));
+
+    let mut best: Option<(Positions, f32, f32)> = None;
+    for (widen, spacing_factor) in steps {
+        let spacing = base_spacing * spacing_factor;
+        let positions = attempt(widen, spacing);
+        let placed_all = positions.len() >= requested;
+        if best.as_ref().is_none_or(|(b, _, _)| positions.len() > b.len()) {
+            best = Some((positions, widen, spacing_factor));
+        }
+        if placed_all {
+            break;
+        }
+    }
+
+    let (positions, widen, spacing_factor) = best.unwrap_or((Vec::new(), 1.0, 1.0));
+    let mut report = PlacementReport {
+        requested,
+        placed: positions.len(),
+        spacing: base_spacing * spacing_factor,
+        relaxations: Vec::new(),
+    };
+    if widen > 1.0 {
+        report.relaxations.push(Relaxation::Widened { factor: widen });
+    }
+    if spacing_factor < 1.0 {
+        report.relaxations.push(Relaxation::ReducedSpacing { spacing: report.spacing });
+    }
+    if !report.is_complete() {
+        report.relaxations.push(Relaxation::PlacedFewer {
+            placed: report.placed,
+            requested,
+        });
+    }
+    (positions, report)
+}
+
 pub fn generate_collectibles(
-    ctx: &mut Context,
-    screen_width: f32,
-    screen_height: f32,
-    collectible_count: i32,
-    cluster_size: f32,
-    proximity_threshold: f32,
-    collectible_size: f32,
-) -> ggez::GameResult<Vec<Collectible>> {
-    let mut collectibles: Vec<Collectible> = Vec::new();
-    let mut rng = rand::thread_rng();
+    field: &FieldSettings,
+    archetypes: &[Archetype],
+    layout: &ClusterLayout,
+    constraints: &PlacementConstraints,
+) -> ggez::GameResult<(Vec<Collectible>, PlacementReport)> {
+    let (plans, report) = plan_from_layout(field, archetypes, layout, constraints)?;
+    let collectibles =
+        spawn_collectibles(&plans, GENERATED_ID_PREFIX, field.collectible_size, field.proximity_threshold);
+    Ok((collectibles, report))
+}
 
-    let cluster_points = collectible_cluster_points::get_cluster_points(
-        ClusterPointCollection::Collection2,
+// Positions around the layout's clusters, plus everything else random about each collectible
+pub fn plan_from_layout(
+    field: &FieldSettings,
+    archetypes: &[Archetype],
+    layout: &ClusterLayout,
+    constraints: &PlacementConstraints,
+) -> ggez::GameResult<(Vec<CollectiblePlan>, PlacementReport)> {
+    let FieldSettings {
         screen_width,
         screen_height,
+        collectible_count,
+        cluster_size,
+        collectible_size,
+        ref seed,
+        ..
+    } = *field;
+    let mut rng = seed.stream(world_seed::PLACEMENT_STREAM);
+
+    let requested = collectible_count.max(0) as usize;
+    let quotas = layout.quotas(requested, &mut rng);
+
+    let (positions, report) = place_with_relaxation(
+        requested,
+        collectible_size * MIN_SPACING_FACTOR,
+        |widen, spacing| {
+            let regions: Vec<Region> = layout
+                .clusters
+                .iter()
+                .zip(&quotas)
+                .map(|(cluster, &quota)| Region {
+                    center: cluster.center(screen_width, screen_height),
+                    spread: cluster.spread_or(cluster_size) * widen,
+                    quota,
+                })
+                .collect();
+            poisson_disk::sample_clusters(
+                &mut rng,
+                &regions,
+                Point2 { x: PLACEMENT_MARGIN, y: PLACEMENT_MARGIN },
+                Point2 {
+                    x: screen_width - PLACEMENT_MARGIN,
+                    y: screen_height - PLACEMENT_MARGIN,
+                },
+                spacing,
+                DEFAULT_ATTEMPTS,
+                &|position| constraints.allows(position, collectible_size / 2.0),
+            )
+            .iter()
+            .map(|sample| (sample.position, Some(sample.cluster)))
+            .collect()
+        },
     );
 
-    for i in 0..collectible_count {
-
-        let mut position_ok = false;
-        let mut failure_count = 0; 
-   
-        // Add randomness to the shapeshifting start point
-        let initial_time = rng.gen_range(0.0..6.28);
-        let id = format!("collect{}", i);
-
-        while !position_ok {
-            position_ok = true;
-            let (center_x, center_y) = cluster_points[rng.gen_range(0..cluster_points.len())];
-            let x = rng
-                .gen_range(center_x - cluster_size..=center_x + cluster_size)
-                .clamp(20.0, screen_width - 20.0); // Assuming 50.0 is the margin
-            let y = rng
-                .gen_range(center_y - cluster_size..=center_y + cluster_size)
-                .clamp(20.0, screen_height - 20.0); // Assuming 50.0 is the margin
-
-
-
-            for existing_collectible in &collectibles {
-                let distance = ((existing_collectible.position.x - x).powi(2)
-                    + (existing_collectible.position.y - y).powi(2))
-                .sqrt();
-                if distance < 3.0 {
-                   
-                    position_ok = false;
-                    failure_count += 1;
-                    println!("too close {} {}", distance, position_ok);
-                    break;
-                }
-            }
-            if failure_count >= 20 {
-                println!("Error: Failed to place collectible after 20 attempts");
-                return Err(ggez::GameError::CustomError("Failed to place collectible".to_string()));
-            }
-            if position_ok {
-                
-                failure_count = 0;
-                let collectible = Collectible::new(
-                    ctx,
-                    x,
-                    y,
-                    collectible_size,
-                    proximity_threshold,
-                    initial_time,
-                    id.clone(),
-                    false,   // Assuming some default values
-                    10000.0, // Assuming some default value for score or similar
-                )?;
+    let spawns: Vec<Spawn> = positions.into_iter().map(Spawn::generated).collect();
+    Ok((plan_spawns(&spawns, archetypes, seed)?, report))
+}
+
+// Scatters collectibles over a noise density map; each dense patch becomes a cluster
+pub fn generate_from_density(
+    field: &FieldSettings,
+    archetypes: &[Archetype],
+    params: &DensityMapParams,
+    constraints: &PlacementConstraints,
+) -> ggez::GameResult<(Vec<Collectible>, PlacementReport)> {
+    let (plans, report) = plan_from_density(field, archetypes, params, constraints)?;
+    let collectibles =
+        spawn_collectibles(&plans, GENERATED_ID_PREFIX, field.collectible_size, field.proximity_threshold);
+    Ok((collectibles, report))
+}
+
+pub fn plan_from_density(
+    field: &FieldSettings,
+    archetypes: &[Archetype],
+    params: &DensityMapParams,
+    constraints: &PlacementConstraints,
+) -> ggez::GameResult<(Vec<CollectiblePlan>, PlacementReport)> {
+    let FieldSettings {
+        screen_width,
+        screen_height,
+        collectible_count,
+        collectible_size,
+        ref seed,
+        ..
+    } = *field;
+    let mut rng = seed.stream(world_seed::PLACEMENT_STREAM);
+    let requested = collectible_count.max(0) as usize;
+    let inside_margin = |position: Point2<f32>| {
+        (PLACEMENT_MARGIN..=screen_width - PLACEMENT_MARGIN).contains(&position.x)
+            && (PLACEMENT_MARGIN..=screen_height - PLACEMENT_MARGIN).contains(&position.y)
+    };
 
+    let (positions, report) = place_with_relaxation(
+        requested,
+        collectible_size * MIN_SPACING_FACTOR,
+        |widen, spacing| {
+            // A lower threshold lets the dense patches grow
+            let params = DensityMapParams {
+                threshold: params.threshold / widen,
+                ..params.clone()
+            };
+            let grid = density_map::preview(&params, seed, screen_width, screen_height);
+            density_map::sample_positions(&mut rng, &grid, requested, spacing, &|position| {
+                inside_margin(position) && constraints.allows(position, collectible_size / 2.0)
+            })
+            .into_iter()
+            .map(|(position, patch)| (position, Some(patch)))
+            .collect()
+        },
+    );
 
+    let spawns: Vec<Spawn> = positions.into_iter().map(Spawn::generated).collect();
+    Ok((plan_spawns(&spawns, archetypes, seed)?, report))
+}
 
+// Collectibles at exactly the given positions, e.g. from a level file.
+// Only their start times and personalities (and kinds, where not given) are random.
+pub fn place_collectibles(
+    placements: &[FixedPlacement],
+    collectible_size: f32,
+    proximity_threshold: f32,
+    archetypes: &[Archetype],
+    seed: &WorldSeed,
+) -> ggez::GameResult<(Vec<Collectible>, PlacementReport)> {
+    let spawns: Vec<Spawn> = placements
+        .iter()
+        .map(|placement| Spawn {
+            position: placement.position,
+            cluster: None,
+            archetype: placement.archetype.as_ref(),
+        })
+        .collect();
+    let plans = plan_spawns(&spawns, archetypes, seed)?;
+    let collectibles = spawn_collectibles(&plans, PLACED_ID_PREFIX, collectible_size, proximity_threshold);
+    let report = PlacementReport {
+        requested: placements.len(),
+        placed: collectibles.len(),
+        spacing: 0.0,
+        relaxations: Vec::new(),
+    };
+    Ok((collectibles, report))
+}
 
-                collectibles.push(collectible);
+// Rolls the start time, kind and personality of every spawn
+fn plan_spawns(spawns: &[Spawn], archetypes: &[Archetype], seed: &WorldSeed) -> ggez::GameResult<Vec<CollectiblePlan>> {
+    if archetypes.is_empty() {
+        return Err(ggez::GameError::CustomError("At least one archetype is needed".to_string()));
+    }
+    let mut time_rng = seed.stream(world_seed::INITIAL_TIME_STREAM);
+    let mut noise_rng = seed.stream(world_seed::NOISE_SEED_STREAM);
+    let mut archetype_rng = seed.stream(world_seed::ARCHETYPE_STREAM);
+
+    let plans = spawns
+        .iter()
+        .map(|spawn| {
+            // Add randomness to the shapeshifting start point
+            let initial_time = time_rng.gen_range(0.0..std::f32::consts::TAU);
+            // Always drawn, so a fixed kind doesn't shift the kinds of the ones after it
+            let drawn = &archetypes[archetype_rng.gen_range(0..archetypes.len())];
+            let archetype = spawn.archetype.unwrap_or(drawn);
+            let personality = Personality::random(&mut noise_rng, &archetype.personality);
+            CollectiblePlan {
+                position: spawn.position,
+                cluster: spawn.cluster,
+                initial_time,
+                archetype: archetype.clone(),
+                personality,
             }
+        })
+        .collect();
+    Ok(plans)
+}
 
+fn spawn_collectibles(
+    plans: &[CollectiblePlan],
+    id_prefix: &str,
+    collectible_size: f32,
+    proximity_threshold: f32,
+) -> Vec<Collectible> {
+    let mut collectibles: Vec<Collectible> = Vec::with_capacity(plans.len());
+    for (i, plan) in plans.iter().enumerate() {
+        let mut collectible = Collectible::new(
+            plan.position,
+            collectible_size,
+            proximity_threshold,
+            plan.initial_time,
+            format!("{}{}", id_prefix, i),
+            plan.archetype.clone(),
+            plan.personality,
+        );
+        collectible.cluster = plan.cluster;
+        collectibles.push(collectible);
+    }
+    collectibles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster_layout::ClusterSpec;
+
+    const WIDTH: f32 = 1600.0;
+    const HEIGHT: f32 = 1200.0;
+    const SIZE: f32 = 60.0;
+
+    fn two_clusters() -> ClusterLayout {
+        let cluster = |x, y| ClusterSpec {
+            x,
+            y,
+            spread: None,
+            count: None,
+            weight: 1.0,
+        };
+        ClusterLayout {
+            name: "test".to_string(),
+            clusters: vec![cluster(0.25, 0.5), cluster(0.75, 0.5)],
         }
+    }
+
+    fn archetypes() -> Vec<Archetype> {
+        let brute = Archetype {
+            name: "brute".to_string(),
+            hit_points: Some(5),
+            ..Archetype::default()
+        };
+        vec![Archetype::default(), brute]
+    }
+
+    fn field(collectible_count: i32, cluster_size: f32, seed: WorldSeed) -> FieldSettings {
+        FieldSettings {
+            screen_width: WIDTH,
+            screen_height: HEIGHT,
+            collectible_count,
+            cluster_size,
+            proximity_threshold: 250.0,
+            collectible_size: SIZE,
+            seed,
+        }
+    }
+
+    fn plan_layout(seed: WorldSeed) -> Vec<CollectiblePlan> {
+        let constraints = PlacementConstraints::new();
+        let (plans, report) =
+            plan_from_layout(&field(12, 200.0, seed), &archetypes(), &two_clusters(), &constraints).unwrap();
+        assert!(report.is_complete());
+        plans
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_layout() {
+        let first = plan_layout(WorldSeed(42));
+        let second = plan_layout(WorldSeed(42));
+        assert_eq!(first.len(), 12);
+        // Positions, start times, personalities and kinds all come from the seed
+        assert_eq!(first, second);
+        assert_ne!(first, plan_layout(WorldSeed(43)));
+    }
+
+    #[test]
+    fn placed_and_generated_ids_do_not_collide() {
+        let seed = WorldSeed(42);
+        let placements: Vec<FixedPlacement> = (0..3)
+            .map(|i| FixedPlacement {
+                position: Point2 { x: 100.0 + i as f32 * 100.0, y: 100.0 },
+                archetype: None,
+            })
+            .collect();
+        let (mut items, _) = place_collectibles(&placements, SIZE, 250.0, &archetypes(), &seed).unwrap();
+        let constraints = PlacementConstraints::new();
+        let (generated, _) =
+            generate_collectibles(&field(12, 200.0, seed), &archetypes(), &two_clusters(), &constraints).unwrap();
+        items.extend(generated);
+
+        let mut ids: Vec<&str> = items.iter().map(|collectible| collectible.id.as_str()).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), items.len());
+    }
+
+    #[test]
+    fn same_seed_replays_the_same_density_field() {
+        let plan = |seed: WorldSeed| {
+            let params = DensityMapParams::default();
+            plan_from_density(&field(8, 0.0, seed), &archetypes(), &params, &PlacementConstraints::new())
+                .unwrap()
+                .0
+        };
+        assert_eq!(plan(WorldSeed(7)), plan(WorldSeed(7)));
+    }
+
+    // Stands in for a crowded field: only wide enough clusters and close enough spacing
+    // fit everything, and `fits` is the most that ever fits
+    fn crowded(fits: usize, tried: &mut Vec<(f32, f32)>, widen: f32, spacing: f32) -> Positions {
+        tried.push((widen, spacing));
+        let room = if widen >= 2.0 && spacing <= 70.0 { fits } else { fits / 2 };
+        (0..room).map(|i| (Point2 { x: i as f32, y: 0.0 }, Some(0))).collect()
+    }
+
+    #[test]
+    fn relaxation_widens_then_tightens_spacing() {
+        let mut tried = Vec::new();
+        let (positions, report) = place_with_relaxation(10, 100.0, |widen, spacing| {
+            crowded(10, &mut tried, widen, spacing)
+        });
+        assert_eq!(tried, vec![(1.0, 100.0), (1.5, 100.0), (2.0, 100.0), (2.0, 85.0), (2.0, 70.0)]);
+        assert_eq!(positions.len(), 10);
+        assert!(report.is_complete());
+        assert_eq!(
+            report.relaxations,
+            vec![Relaxation::Widened { factor: 2.0 }, Relaxation::ReducedSpacing { spacing: 70.0 }]
+        );
+    }
+
+    #[test]
+    fn relaxation_settles_for_fewer_instead_of_failing() {
+        let mut tried = Vec::new();
+        let (positions, report) = place_with_relaxation(10, 100.0, |widen, spacing| {
+            crowded(6, &mut tried, widen, spacing)
+        });
+        assert_eq!(tried.len(), WIDEN_STEPS.len() + SPACING_STEPS.len() - 1);
+        assert_eq!(positions.len(), 6);
+        assert!(!report.is_complete());
+        assert_eq!(report.placed, 6);
+        assert_eq!(report.requested, 10);
+        assert_eq!(
+            report.relaxations,
+            vec![
+                Relaxation::Widened { factor: 2.0 },
+                Relaxation::ReducedSpacing { spacing: 70.0 },
+                Relaxation::PlacedFewer { placed: 6, requested: 10 },
+            ]
+        );
+    }
+
+    #[test]
+    fn roomy_fields_are_not_relaxed() {
+        let (positions, report) = place_with_relaxation(4, 100.0, |_, _| {
+            (0..4).map(|i| (Point2 { x: i as f32, y: 0.0 }, None)).collect()
+        });
+        assert_eq!(positions.len(), 4);
+        assert!(!report.was_relaxed());
+        assert_eq!(report.summary(), "placed 4 of 4 collectibles 100px apart");
+    }
 
+    #[test]
+    fn crowded_layout_reports_a_shortfall() {
+        // Far more than two small clusters can hold, boxed in by a zone over most of the field
+        let mut constraints = PlacementConstraints::new();
+        constraints.exclude_rect(0.0, 0.0, WIDTH, HEIGHT * 0.4);
+        let (plans, report) =
+            plan_from_layout(&field(200, 60.0, WorldSeed(1)), &archetypes(), &two_clusters(), &constraints).unwrap();
+        assert_eq!(plans.len(), report.placed);
+        assert!(report.placed < report.requested);
+        assert!(matches!(report.relaxations.last(), Some(Relaxation::PlacedFewer { requested: 200, .. })));
     }
-    // for i in 0..collectibles.len() {
-    //     println!("{:?}", collectibles[i]);
-    // }
-    Ok(collectibles)
 }
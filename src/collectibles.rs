@@ -1,89 +1,184 @@
+use crate::archetype::Archetype;
+use crate::cluster_layout::ClusterLayout;
 use crate::collectible::Collectible;
 use crate::collidable::Collidable;
-use crate::collectible_placement;
-use crate::smoke_effect::SmokeEffect;
-use ggez::{event, graphics, Context, GameResult};
+use crate::collectible_placement::{self, FieldSettings, FixedPlacement, PlacementReport};
+use crate::density_map::DensityMapParams;
+use crate::placement_constraints::PlacementConstraints;
+use crate::effects::Effects;
+use crate::metaball::{self, MetaBlob};
+use ggez::graphics::{Color, MeshBuilder};
+use ggez::{graphics, Context, GameResult};
 use mint::{Point2, Vector2};
 use noise::utils::PlaneMapBuilder;
 use crate::player::Player;
 use rand::Rng;
+use rand_chacha::ChaCha8Rng;
 use uuid::Uuid;
 use crate::proximity_and_collision_handler::handle_proximity_and_collisions;
+use crate::world_seed;
+
+pub const DEFAULT_MERGE_DISTANCE: f32 = 40.0;
+pub const METABALL_CELL_SIZE: f32 = 8.0;
+pub const NOTICE_DISTANCE: f32 = 500.0; // collectibles closer than this (times aggression) react to the player
+
+// Where a field's collectibles come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpawnSource {
+    // Scattered around the clusters of the current layout
+    Layout,
+    // Scattered over a noise density map
+    Density(DensityMapParams),
+    // Nothing generated, only the fixed placements
+    Fixed,
+}
 
 pub struct Collectibles {
     pub items: Vec<Collectible>,
-    collectible_count: i32,
-    collectible_size: f32,
-    cluster_size: f32,
-    max_distance_threshold: f32,
-    screen_width: f32,
-    screen_height: f32,
+    // Every reset regenerates the same world from the seed in here
+    field: FieldSettings,
+    // Each generated collectible is one of these kinds, picked at random
+    archetypes: Vec<Archetype>,
+    layouts: Vec<ClusterLayout>,
+    layout_index: usize,
+    pub source: SpawnSource,
+    // Always spawned at exactly these positions (e.g. from a level file), next to whatever is generated
+    pub placements: Vec<FixedPlacement>,
+    // Where generated collectibles may not go
+    pub constraints: PlacementConstraints,
+    // How the current field came out, e.g. whether placement had to be relaxed
+    pub placement_report: PlacementReport,
+    // Blobs whose outlines come closer than this are drawn as one merged shape
+    pub merge_distance: f32,
+    // Scales how far away collectibles notice the player
+    pub aggression: f32,
+    split_rng: ChaCha8Rng,
 }
 
 impl Collectibles {
     pub fn new(
-        ctx: &mut Context, 
-        screen_width: f32, 
-        screen_height: f32, 
-        collectible_count: i32, 
-        max_distance_threshold: f32, 
-        cluster_size: f32, 
-        collectible_size: f32
+        field: FieldSettings,
+        archetypes: Vec<Archetype>,
+        layouts: Vec<ClusterLayout>,
+        layout_name: Option<&str>,
+        source: SpawnSource,
+        placements: Vec<FixedPlacement>,
+        constraints: PlacementConstraints,
     ) -> GameResult<Collectibles> {
-        let items = Collectibles::setup_collectibles(
-            ctx,
-            screen_width,
-            screen_height,
-            collectible_count,
-            cluster_size,
-            max_distance_threshold,
-            collectible_size,
-        )?;
+        let layout_index = match layout_name {
+            Some(name) => Collectibles::find_layout(&layouts, name)?,
+            None if layouts.is_empty() => {
+                return Err(ggez::GameError::CustomError("At least one cluster layout is needed".to_string()))
+            }
+            None => 0,
+        };
+        let layout = &layouts[layout_index];
+        let (items, placement_report) =
+            Collectibles::setup_collectibles(&field, &archetypes, layout, &source, &placements, &constraints)?;
         
         Ok(Collectibles {
             items,
-            collectible_count,
-            collectible_size,
-            cluster_size,
-            max_distance_threshold,
-            screen_width,
-            screen_height,
+            field,
+            archetypes,
+            layouts,
+            layout_index,
+            source,
+            placements,
+            constraints,
+            placement_report,
+            merge_distance: DEFAULT_MERGE_DISTANCE,
+            aggression: 1.0,
+            split_rng: field.seed.stream(world_seed::SPLIT_STREAM),
         })
     }
 
-    pub fn reset_collectibles(&mut self, ctx: &mut Context) {
-        self.items = Collectibles::setup_collectibles(
-            ctx,
-            self.screen_width,
-            self.screen_height,
-            self.collectible_count,
-            self.cluster_size,
-            self.max_distance_threshold,
-            self.collectible_size,
-        ).expect("Failed to generate collectibles");
+    // Regenerates the field. Crowded layouts are relaxed rather than failing, so this only
+    // errors if a collectible can't be built; the old field is kept in that case.
+    pub fn reset_collectibles(&mut self) -> GameResult<PlacementReport> {
+        let (items, report) = Collectibles::setup_collectibles(
+            &self.field,
+            &self.archetypes,
+            &self.layouts[self.layout_index],
+            &self.source,
+            &self.placements,
+            &self.constraints,
+        )?;
+        self.items = items;
+        self.placement_report = report.clone();
+        self.split_rng = self.field.seed.stream(world_seed::SPLIT_STREAM);
+        Ok(report)
     }
 
     fn setup_collectibles(
-        ctx: &mut Context,
-        screen_width: f32,
-        screen_height: f32,
-        collectible_count: i32,
-        cluster_size: f32,
-        max_distance_threshold: f32,
-        collectible_size: f32,
-    ) -> GameResult<Vec<Collectible>> {
-        collectible_placement::generate_collectibles(
-            ctx,
-            screen_width,
-            screen_height,
-            collectible_count,
-            cluster_size,
-            max_distance_threshold,
-            collectible_size,
-        )
+        field: &FieldSettings,
+        archetypes: &[Archetype],
+        layout: &ClusterLayout,
+        source: &SpawnSource,
+        placements: &[FixedPlacement],
+        constraints: &PlacementConstraints,
+    ) -> GameResult<(Vec<Collectible>, PlacementReport)> {
+        // The fixed ones get a seed of their own so they don't mirror the first generated ones
+        let (mut items, fixed_report) = collectible_placement::place_collectibles(
+            placements,
+            field.collectible_size,
+            field.proximity_threshold,
+            archetypes,
+            &field.seed.child("placements"),
+        )?;
+        let (generated, mut report) = match source {
+            SpawnSource::Layout => {
+                collectible_placement::generate_collectibles(field, archetypes, layout, constraints)
+            }
+            SpawnSource::Density(params) => {
+                collectible_placement::generate_from_density(field, archetypes, params, constraints)
+            }
+            SpawnSource::Fixed => return Ok((items, fixed_report)),
+        }?;
+        items.extend(generated);
+        report.requested += fixed_report.requested;
+        report.placed += fixed_report.placed;
+        Ok((items, report))
+    }
+
+    // True once every collectible, including split children, has been gathered
+    pub fn is_cleared(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn layout(&self) -> &ClusterLayout {
+        &self.layouts[self.layout_index]
+    }
+
+    pub fn layout_named(&self, name: &str) -> Option<&ClusterLayout> {
+        self.layouts.iter().find(|layout| layout.name == name)
+    }
+
+    fn find_layout(layouts: &[ClusterLayout], name: &str) -> GameResult<usize> {
+        layouts.iter().position(|layout| layout.name == name).ok_or_else(|| {
+            let known: Vec<&str> = layouts.iter().map(|layout| layout.name.as_str()).collect();
+            ggez::GameError::CustomError(format!(
+                "No cluster layout named '{}' (loaded: {})",
+                name,
+                known.join(", ")
+            ))
+        })
+    }
+
+    // Cycles through the loaded layouts in order, switching the field back to layout placement
+    pub fn next_layout(&mut self) -> GameResult<PlacementReport> {
+        self.layout_index = (self.layout_index + 1) % self.layouts.len();
+        self.source = SpawnSource::Layout;
+        self.reset_collectibles()
     }
 
-    pub fn update(&mut self, ctx: &mut Context, dt: f32, player_position: Point2<f32>, player: &Player) {
+    pub fn update(
+        &mut self,
+        ctx: &mut Context,
+        dt: f32,
+        player_position: Point2<f32>,
+        player: &mut Player,
+        effects: &mut Effects,
+    ) -> GameResult {
        
        
         let player_collidable: &dyn Collidable = player;
@@ -100,14 +195,21 @@ impl Collectibles {
             
             self.handle_collectible_proximity(collectible_index, distance);
             if is_collided {
-                self.handle_collectible_collision(collectible_index, &mut to_remove);
+                self.handle_collectible_collision(collectible_index, player_position, &mut to_remove);
             }
         }
 
         for index in to_remove.iter().rev() {
-            if let Some(collectible) = self.items.get_mut(*index) {
-                // collectible.activate_smoke_effect(&mut self.smoke_effect_pool);
-                self.items.remove(*index);
+            if *index < self.items.len() {
+                let collectible = self.items.remove(*index);
+                collectible.activate_smoke_effect(effects);
+                // Power-up kinds grow the magnet when they're gathered
+                if collectible.archetype.magnet_bonus > 0.0 {
+                    player.upgrade_magnet(collectible.archetype.magnet_bonus);
+                }
+                if collectible.can_split() {
+                    self.items.extend(collectible.split(&mut self.split_rng));
+                }
             }
         }
 
@@ -115,43 +217,91 @@ impl Collectibles {
            
             collectible.player_direction = player.direction;
             
-            collectible.update(ctx, dt, player_position);
-            collectible.draw(ctx, player_position);
+            collectible.update(ctx, dt, player_position, player.radius, player.magnet_radius)?;
             
         }
-
-        // Update all smoke effects in the pool
-        // for effect in smoke_effect_pool {
-        //     effect.update(dt);
-        // }
+        Ok(())
     }
 
     pub fn handle_collectible_proximity(&mut self, collectible_index: usize, distance: f32) {
         if let Some(collectible) = self.items.get_mut(collectible_index) {
-            let in_proximity = distance < 1000.0 / 2.0;
+            let in_proximity = distance < NOTICE_DISTANCE * self.aggression;
             collectible.set_in_proximity(
                 in_proximity,
                 distance,
                 1000.0,
             );
+            collectible.on_proximity(distance);
         }
     }
 
     fn handle_collectible_collision(
         &mut self,
         collectible_index: usize,
+        player_position: Point2<f32>,
         to_remove: &mut Vec<usize>,
     ) {
-        to_remove.push(collectible_index);
+        if let Some(collectible) = self.items.get_mut(collectible_index) {
+            collectible.on_collision();
+        }
+        if self.apply_hit(collectible_index, player_position, 1) {
+            to_remove.push(collectible_index);
+        }
+    }
+
+    // Entry point for anything that damages collectibles, e.g. the player or projectiles.
+    // Returns true if the hit was the final one and the collectible should be collected.
+    pub fn apply_hit(&mut self, collectible_index: usize, source: Point2<f32>, damage: u32) -> bool {
+        match self.items.get_mut(collectible_index) {
+            Some(collectible) => collectible.take_hit(source, damage),
+            None => false,
+        }
     }
 
     pub fn draw(&self, ctx: &mut Context, player_position: mint::Point2<f32>) -> GameResult<()> {
-        for collectible in &self.items {
-            println!("im alive! {}", self.items.len());
-            collectible.draw(ctx, player_position)?;
+        let blobs: Vec<MetaBlob> = self.items.iter().map(|c| c.metablob()).collect();
+
+        for group in metaball::group_blobs(&blobs, self.merge_distance) {
+            if group.len() == 1 || !self.draw_merged(ctx, &blobs, &group)? {
+                for index in group {
+                    self.items[index].draw(ctx, player_position)?;
+                }
+            }
         }
         Ok(())
     }
 
+    // Draws a group of nearby collectibles as one gooey shape, then their features on top.
+    // Returns false if the group produced no contour and should be drawn one by one.
+    fn draw_merged(&self, ctx: &mut Context, blobs: &[MetaBlob], group: &[usize]) -> GameResult<bool> {
+        let group_blobs: Vec<MetaBlob> = group.iter().map(|&i| blobs[i]).collect();
+        let contour = metaball::marching_squares(&group_blobs, self.merge_distance, METABALL_CELL_SIZE);
+        if contour.fill.is_empty() {
+            return Ok(false);
+        }
+
+        let mut color = Color::new(0.0, 0.0, 0.0, 0.0);
+        for &index in group {
+            let body_color = self.items[index].body_color();
+            color.r += body_color.r / group.len() as f32;
+            color.g += body_color.g / group.len() as f32;
+            color.b += body_color.b / group.len() as f32;
+            color.a += body_color.a / group.len() as f32;
+        }
+
+        let mut builder = MeshBuilder::new();
+        builder.triangles(&contour.fill, color)?;
+        for (start, end) in &contour.outline {
+            builder.line(&[*start, *end], 2.0, color)?;
+        }
+        let mesh = builder.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
+
+        for &index in group {
+            self.items[index].draw_features(ctx)?;
+        }
+        Ok(true)
+    }
+
   
 }
\ No newline at end of file
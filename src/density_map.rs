@@ -0,0 +1,312 @@
+// Collectible distribution driven by a fractal noise density field. Dense patches turn
+// into clusters on their own, so no cluster centers have to be placed by hand.
+// Nothing in here needs a graphics context, so a seed can be previewed from the terminal.
+use crate::world_seed::{self, WorldSeed};
+use mint::Point2;
+use noise::{Fbm, MultiFractal, NoiseFn, Seedable};
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub const PREVIEW_ARG: &str = "--preview-density";
+pub const PREVIEW_SHADES: &[u8] = b" .:-=+*#%@";
+pub const SAMPLE_ATTEMPTS_PER_POINT: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DensityMapParams {
+    // Layers of noise; more octaves give rougher, more detailed patches
+    #[serde(default = "default_octaves")]
+    pub octaves: usize,
+    // Rough size of a patch in pixels
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    // How much each octave contributes compared to the one before
+    #[serde(default = "default_persistence")]
+    pub persistence: f64,
+    // Density below this (0.0..1.0) is treated as empty ground
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+    // Resolution of the sampled grid in pixels
+    #[serde(default = "default_cell_size")]
+    pub cell_size: f32,
+}
+
+fn default_octaves() -> usize {
+    4
+}
+fn default_scale() -> f64 {
+    600.0
+}
+fn default_persistence() -> f64 {
+    0.5
+}
+fn default_threshold() -> f32 {
+    0.55
+}
+fn default_cell_size() -> f32 {
+    20.0
+}
+
+impl Default for DensityMapParams {
+    fn default() -> Self {
+        DensityMapParams {
+            octaves: default_octaves(),
+            scale: default_scale(),
+            persistence: default_persistence(),
+            threshold: default_threshold(),
+            cell_size: default_cell_size(),
+        }
+    }
+}
+
+impl DensityMapParams {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.octaves == 0 || self.octaves > Fbm::MAX_OCTAVES {
+            return Err(format!("density octaves must be 1 to {}, got {}", Fbm::MAX_OCTAVES, self.octaves));
+        }
+        if self.scale.is_nan() || self.scale <= 0.0 {
+            return Err(format!("density scale must be positive, got {}", self.scale));
+        }
+        if !(0.0..1.0).contains(&self.threshold) {
+            return Err(format!("density threshold must be at least 0.0 and below 1.0, got {}", self.threshold));
+        }
+        if self.cell_size.is_nan() || self.cell_size <= 0.0 {
+            return Err(format!("density cell_size must be positive, got {}", self.cell_size));
+        }
+        Ok(())
+    }
+}
+
+// The sampled field. Values are 0.0 below the threshold and rise to 1.0 in the densest spots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityGrid {
+    pub columns: usize,
+    pub rows: usize,
+    pub cell_size: f32,
+    pub values: Vec<f32>,
+}
+
+impl DensityGrid {
+    pub fn at(&self, column: usize, row: usize) -> f32 {
+        self.values[row * self.columns + column]
+    }
+
+    // Labels every non-empty cell with the index of the connected patch it belongs to
+    pub fn regions(&self) -> (Vec<Option<usize>>, usize) {
+        let mut labels = vec![None; self.values.len()];
+        let mut count = 0;
+        for start in 0..self.values.len() {
+            if self.values[start] <= 0.0 || labels[start].is_some() {
+                continue;
+            }
+            let mut stack = vec![start];
+            labels[start] = Some(count);
+            while let Some(cell) = stack.pop() {
+                let (column, row) = (cell % self.columns, cell / self.columns);
+                let mut neighbours = Vec::with_capacity(4);
+                if column > 0 {
+                    neighbours.push(cell - 1);
+                }
+                if column + 1 < self.columns {
+                    neighbours.push(cell + 1);
+                }
+                if row > 0 {
+                    neighbours.push(cell - self.columns);
+                }
+                if row + 1 < self.rows {
+                    neighbours.push(cell + self.columns);
+                }
+                for next in neighbours {
+                    if self.values[next] > 0.0 && labels[next].is_none() {
+                        labels[next] = Some(count);
+                        stack.push(next);
+                    }
+                }
+            }
+            count += 1;
+        }
+        (labels, count)
+    }
+
+    // One character per cell, darker where denser
+    pub fn to_ascii(&self) -> String {
+        let mut text = String::with_capacity((self.columns + 1) * self.rows);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let shade = (self.at(column, row) * (PREVIEW_SHADES.len() - 1) as f32).round() as usize;
+                text.push(PREVIEW_SHADES[shade.min(PREVIEW_SHADES.len() - 1)] as char);
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+// Samples the density field over a `width` x `height` world. The same params and seed
+// always give the same grid, so designers can check a seed before playing it.
+pub fn preview(params: &DensityMapParams, seed: &WorldSeed, width: f32, height: f32) -> DensityGrid {
+    let noise_seed: u32 = seed.stream(world_seed::DENSITY_STREAM).gen();
+    let fbm = Fbm::new()
+        .set_seed(noise_seed)
+        .set_octaves(params.octaves)
+        .set_frequency(1.0 / params.scale)
+        .set_persistence(params.persistence);
+
+    let cell_size = params.cell_size.max(1.0);
+    let columns = (width / cell_size).ceil().max(1.0) as usize;
+    let rows = (height / cell_size).ceil().max(1.0) as usize;
+    let mut values = Vec::with_capacity(columns * rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let x = (column as f32 + 0.5) * cell_size;
+            let y = (row as f32 + 0.5) * cell_size;
+            let raw = ((fbm.get([x as f64, y as f64]) as f32 + 1.0) / 2.0).clamp(0.0, 1.0);
+            let density = if raw < params.threshold {
+                0.0
+            } else {
+                (raw - params.threshold) / (1.0 - params.threshold)
+            };
+            values.push(density);
+        }
+    }
+    DensityGrid {
+        columns,
+        rows,
+        cell_size,
+        values,
+    }
+}
+
+// Picks up to `count` positions, more of them where the field is denser and none closer than
+// `min_spacing` to each other or rejected by `accept`. Each comes with the index of the
+// patch it landed in. Returns fewer than `count` if the dense ground runs out.
+pub fn sample_positions<R: Rng + ?Sized>(
+    rng: &mut R,
+    grid: &DensityGrid,
+    count: usize,
+    min_spacing: f32,
+    accept: &dyn Fn(Point2<f32>) -> bool,
+) -> Vec<(Point2<f32>, usize)> {
+    let mut positions: Vec<(Point2<f32>, usize)> = Vec::with_capacity(count);
+    let distribution = match WeightedIndex::new(&grid.values) {
+        Ok(distribution) => distribution,
+        Err(_) => return positions, // nothing above the threshold
+    };
+    let (labels, _) = grid.regions();
+
+    let mut attempts = count * SAMPLE_ATTEMPTS_PER_POINT;
+    while positions.len() < count && attempts > 0 {
+        attempts -= 1;
+        let cell = distribution.sample(rng);
+        let (column, row) = (cell % grid.columns, cell / grid.columns);
+        let candidate = Point2 {
+            x: (column as f32 + rng.gen_range(0.0..1.0)) * grid.cell_size,
+            y: (row as f32 + rng.gen_range(0.0..1.0)) * grid.cell_size,
+        };
+        let crowded = positions.iter().any(|(other, _)| {
+            let dx = other.x - candidate.x;
+            let dy = other.y - candidate.y;
+            dx * dx + dy * dy < min_spacing * min_spacing
+        });
+        if !crowded && accept(candidate) {
+            positions.push((candidate, labels[cell].unwrap_or(0)));
+        }
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const WIDTH: f32 = 1600.0;
+    const HEIGHT: f32 = 1200.0;
+
+    fn with_threshold(threshold: f32) -> DensityMapParams {
+        DensityMapParams {
+            threshold,
+            ..DensityMapParams::default()
+        }
+    }
+
+    #[test]
+    fn same_params_and_seed_give_the_same_grid() {
+        let params = DensityMapParams::default();
+        let grid = preview(&params, &WorldSeed(3), WIDTH, HEIGHT);
+        assert_eq!(grid, preview(&params, &WorldSeed(3), WIDTH, HEIGHT));
+        assert_ne!(grid, preview(&params, &WorldSeed(4), WIDTH, HEIGHT));
+        assert_eq!(grid.values.len(), grid.columns * grid.rows);
+    }
+
+    // Raising the threshold can split a patch in two, so it's the ground covered by
+    // regions that only ever shrinks, not necessarily the number of them
+    #[test]
+    fn higher_threshold_never_adds_region_cells() {
+        for seed in 0..10 {
+            let mut lower: Option<Vec<Option<usize>>> = None;
+            for threshold in [0.3, 0.5, 0.7, 0.9] {
+                let grid = preview(&with_threshold(threshold), &WorldSeed(seed), WIDTH, HEIGHT);
+                let (labels, regions) = grid.regions();
+                assert!(labels.iter().flatten().all(|&region| region < regions));
+                if let Some(lower) = &lower {
+                    assert!(labels.iter().zip(lower).all(|(label, lower)| label.is_none() || lower.is_some()));
+                }
+                lower = Some(labels);
+            }
+        }
+    }
+
+    #[test]
+    fn regions_are_connected_patches() {
+        let grid = DensityGrid {
+            columns: 4,
+            rows: 3,
+            cell_size: 10.0,
+            #[rustfmt::skip]
+            values: vec![
+                0.5, 0.5, 0.0, 0.2,
+                0.0, 0.5, 0.0, 0.2,
+                0.0, 0.0, 0.0, 0.0,
+            ],
+        };
+        let (labels, regions) = grid.regions();
+        assert_eq!(regions, 2);
+        assert_eq!(labels[0], labels[5]);
+        assert_ne!(labels[0], labels[3]);
+        assert_eq!(labels[2], None);
+    }
+
+    #[test]
+    fn samples_only_land_above_the_threshold() {
+        let grid = preview(&with_threshold(0.6), &WorldSeed(11), WIDTH, HEIGHT);
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        let samples = sample_positions(&mut rng, &grid, 30, 40.0, &|_| true);
+        assert!(!samples.is_empty());
+        for (position, _) in &samples {
+            let column = (position.x / grid.cell_size) as usize;
+            let row = (position.y / grid.cell_size) as usize;
+            assert!(grid.at(column, row) > 0.0);
+        }
+        for (i, (a, _)) in samples.iter().enumerate() {
+            for (b, _) in &samples[i + 1..] {
+                assert!(((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt() >= 40.0);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_grid_gives_no_samples() {
+        let grid = DensityGrid {
+            columns: 2,
+            rows: 2,
+            cell_size: 10.0,
+            values: vec![0.0; 4],
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        assert!(sample_positions(&mut rng, &grid, 5, 1.0, &|_| true).is_empty());
+    }
+}
@@ -0,0 +1,557 @@
+// In-game level editor. While it's open the game is paused and every change goes to a
+// working copy of the level, which is only played once the editor is closed again.
+use crate::button::Button;
+use crate::cluster_layout::{ClusterLayout, ClusterSpec};
+use crate::level::{GeneratorParams, Level, Placement, Position, COLLECTIBLES_PER_CLUSTER, DEFAULT_CLUSTER_SIZE};
+use ggez::graphics::{self, Color, DrawMode, MeshBuilder};
+use ggez::input::keyboard::KeyCode;
+use ggez::input::mouse::MouseButton;
+use ggez::{Context, GameResult};
+use mint::{Point2, Vector2};
+
+pub const EDITOR_KEY: KeyCode = KeyCode::Tab;
+pub const GRAB_RADIUS: f32 = 20.0; // how close to a marker a click has to be to pick it up
+pub const TOOLBAR_POSITION: Point2<f32> = Point2 { x: 20.0, y: 60.0 };
+pub const TOOLBAR_BUTTON_SIZE: Vector2<f32> = Vector2 { x: 120.0, y: 40.0 };
+pub const TOOLBAR_SPACING: f32 = 10.0;
+pub const MESSAGE_COLOR: Color = Color::new(1.0, 0.4, 0.3, 1.0);
+pub const PLACEMENT_COLOR: Color = Color::new(1.0, 0.85, 0.2, 1.0);
+pub const CLUSTER_COLOR: Color = Color::new(0.3, 0.7, 1.0, 1.0);
+pub const SPAWN_COLOR: Color = Color::new(0.3, 1.0, 0.4, 1.0);
+pub const SPAWN_MARKER_RADIUS: f32 = 25.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tool {
+    Collectible,
+    Cluster,
+    Spawn,
+    Delete,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 4] = [Tool::Collectible, Tool::Cluster, Tool::Spawn, Tool::Delete];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Tool::Collectible => "Collectible",
+            Tool::Cluster => "Cluster",
+            Tool::Spawn => "Spawn",
+            Tool::Delete => "Delete",
+        }
+    }
+
+    // Number keys pick tools in toolbar order
+    pub fn for_key(keycode: KeyCode) -> Option<Tool> {
+        match keycode {
+            KeyCode::Key1 => Some(Tool::Collectible),
+            KeyCode::Key2 => Some(Tool::Cluster),
+            KeyCode::Key3 => Some(Tool::Spawn),
+            KeyCode::Key4 => Some(Tool::Delete),
+            _ => None,
+        }
+    }
+}
+
+// Something on the field the editor can pick up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handle {
+    Placement(usize),
+    Cluster(usize),
+    Spawn,
+}
+
+// What the editor wants its owner to do after a click
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditorCommand {
+    Save,
+}
+
+// The level being edited and the edits themselves. Holds no graphics, so the toolbar
+// and drawing live in `Editor` and this can be driven on its own.
+#[derive(Debug, Clone)]
+pub struct LevelDraft {
+    pub level: Level,
+    // Parallel to the generator's clusters: true for the ones added in the editor. Only
+    // those added to the generated count, so only those take it away again.
+    created: Vec<bool>,
+}
+
+impl LevelDraft {
+    // Clusters from a layout file are copied into the level so they can be moved; the
+    // file itself is left alone
+    pub fn new(level: &Level, layout: Option<&ClusterLayout>) -> Self {
+        let mut level = level.clone();
+        if let (Some(generator), Some(layout)) = (level.collectibles.generate.as_mut(), layout) {
+            if generator.layout.as_deref() == Some(layout.name.as_str()) {
+                generator.layout = None;
+                generator.clusters = layout.clusters.clone();
+            }
+        }
+        let created = vec![false; level.collectibles.generate.as_ref().map_or(0, |g| g.clusters.len())];
+        LevelDraft { level, created }
+    }
+
+    // Puts down whatever `tool` makes at `position` and returns a handle to it. Fails,
+    // changing nothing, if the level can't take it.
+    pub fn add(&mut self, tool: Tool, position: Point2<f32>) -> Result<Option<Handle>, String> {
+        let world = self.level.world;
+        let position = Position {
+            x: position.x.clamp(0.0, world.width),
+            y: position.y.clamp(0.0, world.height),
+        };
+        match tool {
+            Tool::Collectible => {
+                self.level.collectibles.placements.push(Placement {
+                    x: position.x,
+                    y: position.y,
+                    archetype: None,
+                });
+                Ok(Some(Handle::Placement(self.level.collectibles.placements.len() - 1)))
+            }
+            Tool::Cluster => {
+                // Clusters would replace how the field is generated now, so leave that to the level file
+                if let Some(generator) = &self.level.collectibles.generate {
+                    if generator.density.is_some() {
+                        return Err("This level is generated from a density map; remove `density` from the level file to place clusters".to_string());
+                    }
+                    if let Some(layout) = &generator.layout {
+                        return Err(format!(
+                            "This level uses the layout file '{}'; remove `layout` from the level file to place clusters",
+                            layout
+                        ));
+                    }
+                }
+                let generator = self.level.collectibles.generate.get_or_insert_with(|| GeneratorParams {
+                    layout: None,
+                    clusters: Vec::new(),
+                    count: 0,
+                    cluster_size: DEFAULT_CLUSTER_SIZE,
+                    density: None,
+                });
+                generator.clusters.push(ClusterSpec {
+                    x: position.x / world.width,
+                    y: position.y / world.height,
+                    spread: None,
+                    count: None,
+                    weight: 1.0,
+                });
+                generator.count += COLLECTIBLES_PER_CLUSTER;
+                self.created.push(true);
+                Ok(Some(Handle::Cluster(generator.clusters.len() - 1)))
+            }
+            Tool::Spawn => {
+                self.level.player_spawn = position;
+                Ok(Some(Handle::Spawn))
+            }
+            Tool::Delete => Ok(None),
+        }
+    }
+
+    // Removes whatever is under `position`, except the spawn
+    pub fn delete_at(&mut self, position: Point2<f32>) {
+        let handle = self.handle_at(position);
+        let collectibles = &mut self.level.collectibles;
+        match handle {
+            Some(Handle::Placement(i)) => {
+                collectibles.placements.remove(i);
+            }
+            Some(Handle::Cluster(i)) => {
+                if let Some(generator) = collectibles.generate.as_mut() {
+                    generator.clusters.remove(i);
+                    if self.created.remove(i) {
+                        generator.count = (generator.count - COLLECTIBLES_PER_CLUSTER).max(0);
+                    }
+                    // Without clusters the generator has nothing left to scatter around
+                    if generator.clusters.is_empty() {
+                        collectibles.generate = None;
+                    }
+                }
+            }
+            // There is always exactly one spawn; it can only be moved
+            Some(Handle::Spawn) | None => {}
+        }
+    }
+
+    pub fn move_handle(&mut self, handle: Handle, position: Point2<f32>) {
+        let world = self.level.world;
+        let position = Position {
+            x: position.x.clamp(0.0, world.width),
+            y: position.y.clamp(0.0, world.height),
+        };
+        match handle {
+            Handle::Placement(i) => {
+                if let Some(placement) = self.level.collectibles.placements.get_mut(i) {
+                    placement.x = position.x;
+                    placement.y = position.y;
+                }
+            }
+            Handle::Cluster(i) => {
+                let cluster = self
+                    .level
+                    .collectibles
+                    .generate
+                    .as_mut()
+                    .and_then(|generator| generator.clusters.get_mut(i));
+                if let Some(cluster) = cluster {
+                    cluster.x = position.x / world.width;
+                    cluster.y = position.y / world.height;
+                }
+            }
+            Handle::Spawn => self.level.player_spawn = position,
+        }
+    }
+
+    // The closest marker within grab range of `position`
+    pub fn handle_at(&self, position: Point2<f32>) -> Option<Handle> {
+        let mut closest: Option<(Handle, f32)> = None;
+        for (handle, marker) in self.markers() {
+            let distance = ((marker.x - position.x).powi(2) + (marker.y - position.y).powi(2)).sqrt();
+            if distance <= GRAB_RADIUS && closest.is_none_or(|(_, best)| distance < best) {
+                closest = Some((handle, distance));
+            }
+        }
+        closest.map(|(handle, _)| handle)
+    }
+
+    pub fn markers(&self) -> Vec<(Handle, Point2<f32>)> {
+        let world = self.level.world;
+        let mut markers = vec![(Handle::Spawn, self.level.player_spawn.into())];
+        for (i, placement) in self.level.collectibles.placements.iter().enumerate() {
+            markers.push((Handle::Placement(i), placement.into()));
+        }
+        if let Some(generator) = &self.level.collectibles.generate {
+            for (i, cluster) in generator.clusters.iter().enumerate() {
+                markers.push((Handle::Cluster(i), cluster.center(world.width, world.height)));
+            }
+        }
+        markers
+    }
+}
+
+pub struct Editor {
+    pub active: bool,
+    pub tool: Tool,
+    pub draft: LevelDraft,
+    dragging: Option<Handle>,
+    tool_buttons: Vec<(Tool, Button)>,
+    save_button: Button,
+    // Why the last click didn't do anything, shown under the toolbar
+    message: Option<graphics::Text>,
+}
+
+impl Editor {
+    pub fn new(ctx: &mut Context, level: &Level) -> GameResult<Editor> {
+        let step = TOOLBAR_BUTTON_SIZE.x + TOOLBAR_SPACING;
+        let mut tool_buttons = Vec::with_capacity(Tool::ALL.len());
+        for (i, tool) in Tool::ALL.iter().enumerate() {
+            let position = Point2 {
+                x: TOOLBAR_POSITION.x + step * i as f32,
+                y: TOOLBAR_POSITION.y,
+            };
+            tool_buttons.push((*tool, Button::new(ctx, position, TOOLBAR_BUTTON_SIZE, tool.label())?));
+        }
+        let save_position = Point2 {
+            x: TOOLBAR_POSITION.x + step * Tool::ALL.len() as f32,
+            y: TOOLBAR_POSITION.y,
+        };
+        let save_button = Button::new(ctx, save_position, TOOLBAR_BUTTON_SIZE, "Save")?;
+
+        let mut editor = Editor {
+            active: false,
+            tool: Tool::Collectible,
+            draft: LevelDraft::new(level, None),
+            dragging: None,
+            tool_buttons,
+            save_button,
+            message: None,
+        };
+        editor.select_tool(Tool::Collectible);
+        Ok(editor)
+    }
+
+    // Starts editing a fresh copy of `level`
+    pub fn open(&mut self, level: &Level, layout: Option<&ClusterLayout>) {
+        self.draft = LevelDraft::new(level, layout);
+        self.dragging = None;
+        self.message = None;
+        self.active = true;
+    }
+
+    pub fn close(&mut self) {
+        self.dragging = None;
+        self.active = false;
+    }
+
+    // The level as edited so far
+    pub fn level(&self) -> &Level {
+        &self.draft.level
+    }
+
+    pub fn select_tool(&mut self, tool: Tool) {
+        self.tool = tool;
+        // The hover shade marks the selected tool
+        for (button_tool, button) in &mut self.tool_buttons {
+            button.is_hovered = *button_tool == tool;
+        }
+    }
+
+    pub fn mouse_button_down(&mut self, button: MouseButton, position: Point2<f32>) -> Option<EditorCommand> {
+        self.message = None;
+        if button == MouseButton::Right {
+            self.draft.delete_at(position);
+            self.dragging = None;
+            return None;
+        }
+        if button != MouseButton::Left {
+            return None;
+        }
+
+        if self.save_button.is_clicked(position) {
+            return Some(EditorCommand::Save);
+        }
+        let clicked_tool = self
+            .tool_buttons
+            .iter()
+            .find(|(_, button)| button.is_clicked(position))
+            .map(|(tool, _)| *tool);
+        if let Some(tool) = clicked_tool {
+            self.select_tool(tool);
+            return None;
+        }
+
+        if self.tool == Tool::Delete {
+            self.draft.delete_at(position);
+            self.dragging = None;
+            return None;
+        }
+        // Clicking on something picks it up, whatever the tool; empty ground gets a new one
+        self.dragging = match self.draft.handle_at(position) {
+            Some(handle) => Some(handle),
+            None => match self.draft.add(self.tool, position) {
+                Ok(handle) => handle,
+                Err(message) => {
+                    self.message = Some(graphics::Text::new(message));
+                    None
+                }
+            },
+        };
+        None
+    }
+
+    pub fn mouse_button_up(&mut self) {
+        self.dragging = None;
+    }
+
+    pub fn mouse_motion(&mut self, position: Point2<f32>) {
+        if let Some(handle) = self.dragging {
+            self.draft.move_handle(handle, position);
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let level = self.level();
+        let world = level.world;
+        let collectibles = &level.collectibles;
+        let mut builder = MeshBuilder::new();
+
+        if let Some(generator) = &collectibles.generate {
+            for cluster in &generator.clusters {
+                let center = cluster.center(world.width, world.height);
+                let spread = cluster.spread_or(generator.cluster_size).max(1.0);
+                builder.circle(DrawMode::stroke(2.0), center, spread, 1.0, CLUSTER_COLOR)?;
+                builder.line(
+                    &[Point2 { x: center.x - GRAB_RADIUS, y: center.y }, Point2 { x: center.x + GRAB_RADIUS, y: center.y }],
+                    2.0,
+                    CLUSTER_COLOR,
+                )?;
+                builder.line(
+                    &[Point2 { x: center.x, y: center.y - GRAB_RADIUS }, Point2 { x: center.x, y: center.y + GRAB_RADIUS }],
+                    2.0,
+                    CLUSTER_COLOR,
+                )?;
+            }
+        }
+        for placement in &collectibles.placements {
+            let center: Point2<f32> = placement.into();
+            builder.circle(DrawMode::stroke(2.0), center, collectibles.size / 2.0, 1.0, PLACEMENT_COLOR)?;
+        }
+        builder.circle(DrawMode::fill(), level.player_spawn, SPAWN_MARKER_RADIUS, 1.0, SPAWN_COLOR)?;
+        let mesh = builder.build(ctx)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::default())?;
+
+        for (_, button) in &self.tool_buttons {
+            button.draw(ctx)?;
+        }
+        if let Some(message) = &self.message {
+            let position = Point2 {
+                x: TOOLBAR_POSITION.x,
+                y: TOOLBAR_POSITION.y + TOOLBAR_BUTTON_SIZE.y + TOOLBAR_SPACING,
+            };
+            graphics::draw(ctx, message, graphics::DrawParam::default().dest(position).color(MESSAGE_COLOR))?;
+        }
+        self.save_button.draw(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32, y: f32) -> Point2<f32> {
+        Point2 { x, y }
+    }
+
+    // A 1000x1000 level with the spawn in the middle and nothing else
+    fn empty_level() -> Level {
+        Level::parse(
+            r#"{
+                "version": 1,
+                "name": "blank",
+                "world": { "width": 1000, "height": 1000 },
+                "player_spawn": { "x": 500, "y": 500 },
+                "obstacles": [],
+                "collectibles": { "size": 60, "placements": [ { "x": 100, "y": 100 } ] }
+            }"#,
+            "blank.json",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn adds_placements_inside_the_world() {
+        let mut draft = LevelDraft::new(&empty_level(), None);
+        assert_eq!(draft.add(Tool::Collectible, at(300.0, 200.0)), Ok(Some(Handle::Placement(1))));
+        assert_eq!(draft.add(Tool::Collectible, at(1200.0, -40.0)), Ok(Some(Handle::Placement(2))));
+        let placements = &draft.level.collectibles.placements;
+        assert_eq!((placements[1].x, placements[1].y), (300.0, 200.0));
+        assert_eq!((placements[2].x, placements[2].y), (1000.0, 0.0));
+        assert!(draft.level.validate().is_ok());
+    }
+
+    #[test]
+    fn drags_what_it_grabs() {
+        let mut draft = LevelDraft::new(&empty_level(), None);
+        let handle = draft.handle_at(at(110.0, 95.0)).unwrap();
+        assert_eq!(handle, Handle::Placement(0));
+        draft.move_handle(handle, at(700.0, 800.0));
+        let placement = &draft.level.collectibles.placements[0];
+        assert_eq!((placement.x, placement.y), (700.0, 800.0));
+
+        // Nothing within grab range of empty ground
+        assert_eq!(draft.handle_at(at(300.0, 300.0)), None);
+        draft.move_handle(Handle::Spawn, at(50.0, 60.0));
+        assert_eq!(draft.level.player_spawn, Position { x: 50.0, y: 60.0 });
+    }
+
+    #[test]
+    fn deletes_what_is_under_the_cursor() {
+        let mut draft = LevelDraft::new(&empty_level(), None);
+        draft.add(Tool::Collectible, at(300.0, 300.0)).unwrap();
+        draft.delete_at(at(100.0, 100.0));
+        let placements = &draft.level.collectibles.placements;
+        assert_eq!(placements.len(), 1);
+        assert_eq!((placements[0].x, placements[0].y), (300.0, 300.0));
+        // Empty ground is left alone
+        draft.delete_at(at(900.0, 900.0));
+        assert_eq!(draft.level.collectibles.placements.len(), 1);
+    }
+
+    #[test]
+    fn spawn_cannot_be_deleted() {
+        let mut draft = LevelDraft::new(&empty_level(), None);
+        draft.delete_at(at(500.0, 500.0));
+        assert_eq!(draft.level.player_spawn, Position { x: 500.0, y: 500.0 });
+        assert_eq!(draft.handle_at(at(500.0, 500.0)), Some(Handle::Spawn));
+    }
+
+    #[test]
+    fn removing_the_last_cluster_drops_the_generator() {
+        let mut draft = LevelDraft::new(&empty_level(), None);
+        assert_eq!(draft.add(Tool::Cluster, at(250.0, 750.0)), Ok(Some(Handle::Cluster(0))));
+        draft.add(Tool::Cluster, at(750.0, 250.0)).unwrap();
+        let generator = draft.level.collectibles.generate.as_ref().unwrap();
+        assert_eq!(generator.clusters.len(), 2);
+        assert_eq!((generator.clusters[0].x, generator.clusters[0].y), (0.25, 0.75));
+        assert!(draft.level.validate().is_ok());
+
+        draft.delete_at(at(250.0, 750.0));
+        assert_eq!(draft.level.collectibles.generate.as_ref().unwrap().clusters.len(), 1);
+        draft.delete_at(at(750.0, 250.0));
+        assert!(draft.level.collectibles.generate.is_none());
+        assert!(draft.level.validate().is_ok());
+    }
+
+    fn level_with_layout(name: &str) -> Level {
+        let mut level = empty_level();
+        level.collectibles.generate = Some(GeneratorParams {
+            layout: Some(name.to_string()),
+            clusters: Vec::new(),
+            count: 4,
+            cluster_size: 200.0,
+            density: None,
+        });
+        level
+    }
+
+    fn rows_layout() -> ClusterLayout {
+        let cluster = |x| ClusterSpec {
+            x,
+            y: 0.2,
+            spread: None,
+            count: None,
+            weight: 1.0,
+        };
+        ClusterLayout {
+            name: "rows".to_string(),
+            clusters: vec![cluster(0.5), cluster(0.8)],
+        }
+    }
+
+    #[test]
+    fn layout_clusters_are_copied_into_the_level() {
+        let layout = rows_layout();
+        let draft = LevelDraft::new(&level_with_layout("rows"), Some(&layout));
+        let generator = draft.level.collectibles.generate.as_ref().unwrap();
+        assert_eq!(generator.layout, None);
+        assert_eq!(generator.clusters, layout.clusters);
+        assert_eq!(draft.handle_at(at(500.0, 200.0)), Some(Handle::Cluster(0)));
+    }
+
+    #[test]
+    fn only_new_clusters_change_the_count() {
+        let layout = rows_layout();
+        let mut draft = LevelDraft::new(&level_with_layout("rows"), Some(&layout));
+        draft.add(Tool::Cluster, at(200.0, 900.0)).unwrap();
+        assert_eq!(draft.level.collectibles.generate.as_ref().unwrap().count, 4 + COLLECTIBLES_PER_CLUSTER);
+
+        // The copied clusters were already counted by the level
+        draft.delete_at(at(500.0, 200.0));
+        draft.delete_at(at(800.0, 200.0));
+        assert_eq!(draft.level.collectibles.generate.as_ref().unwrap().count, 4 + COLLECTIBLES_PER_CLUSTER);
+        draft.delete_at(at(200.0, 900.0));
+        assert!(draft.level.collectibles.generate.is_none());
+    }
+
+    #[test]
+    fn clusters_are_refused_on_generated_fields() {
+        let mut level = empty_level();
+        level.collectibles.generate = Some(GeneratorParams {
+            layout: None,
+            clusters: Vec::new(),
+            count: 4,
+            cluster_size: 200.0,
+            density: Some(Default::default()),
+        });
+        let mut draft = LevelDraft::new(&level, None);
+        assert!(draft.add(Tool::Cluster, at(300.0, 300.0)).unwrap_err().contains("density map"));
+        assert_eq!(draft.level, level);
+
+        // A layout that wasn't copied in stays the level file's business too
+        let level = level_with_layout("rows");
+        let mut draft = LevelDraft::new(&level, None);
+        assert!(draft.add(Tool::Cluster, at(300.0, 300.0)).unwrap_err().contains("'rows'"));
+        assert_eq!(draft.level, level);
+        // Everything else still works
+        assert!(draft.add(Tool::Collectible, at(300.0, 300.0)).is_ok());
+    }
+}
@@ -0,0 +1,53 @@
+use crate::smoke_effect::SmokeEffect;
+use crate::world_seed::{self, WorldSeed};
+use ggez::graphics::Color;
+use ggez::{Context, GameResult};
+use mint::Point2;
+use rand_chacha::ChaCha8Rng;
+
+pub const SMOKE_PUFFS_PER_BURST: usize = 5;
+
+// Owns every pooled visual effect so gameplay code can fire one off
+// without knowing how the pool is laid out.
+pub struct Effects {
+    smoke_effect_pool: Vec<SmokeEffect>,
+    rng: ChaCha8Rng,
+}
+
+impl Effects {
+    pub fn new(pool_size: i32, seed: &WorldSeed) -> Self {
+        let mut smoke_effect_pool = Vec::new();
+        for _ in 0..pool_size {
+            smoke_effect_pool.push(SmokeEffect::new_inactive());
+        }
+        Effects {
+            smoke_effect_pool,
+            rng: seed.stream(world_seed::SMOKE_STREAM),
+        }
+    }
+
+    // Fires a puff of smoke at `position`, tinted with `color`.
+    // Silently uses fewer puffs if the pool is exhausted.
+    pub fn burst(&mut self, position: Point2<f32>, color: Color) {
+        for _ in 0..SMOKE_PUFFS_PER_BURST {
+            if let Some(inactive_effect) = self.smoke_effect_pool.iter_mut().find(|e| !e.is_active()) {
+                inactive_effect.activate(position, color, &mut self.rng);
+            }
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for effect in &mut self.smoke_effect_pool {
+            effect.update(dt);
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        for effect in &self.smoke_effect_pool {
+            if effect.is_active() {
+                effect.draw(ctx)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,61 +1,200 @@
 use ggez::graphics::{Color, MeshBuilder};
 use ggez::{graphics, Context, GameResult};
 use mint::{Point2, Vector2};
+use rand_chacha::ChaCha8Rng;
+use rand::Rng;
+
+pub const DEFAULT_BLINK_RATE: f32 = 0.25; // average blinks per second
+pub const BLINK_DURATION: f32 = 0.15;
+pub const GAZE_SMOOTHING: f32 = 8.0; // how quickly the gaze catches up, per second
+pub const SACCADE_SIZE: f32 = 0.25; // as a fraction of the full gaze offset
+pub const MIN_SACCADE_INTERVAL: f32 = 0.2;
+pub const MAX_SACCADE_INTERVAL: f32 = 1.5;
+pub const MIN_PUPIL_SIZE: f32 = 0.2; // as a fraction of the eye's scale
+pub const MAX_PUPIL_SIZE: f32 = 0.55;
+pub const PUPIL_SMOOTHING: f32 = 3.0;
+// How far an eye drifts towards the player, in eye radii, unless told otherwise
+pub const GAZE_TRAVEL: f32 = 4.0;
+
+// Procedural eye motion: blinking, pupil dilation, saccades and smoothed gaze.
+// Holds no graphics resources so it can be stepped on its own.
+#[derive(Debug, Clone)]
+pub struct EyeAnimation {
+    pub blink_rate: f32,
+    // 0.0 is shut, 1.0 is wide open
+    pub openness: f32,
+    // 0.0 is a pinprick, 1.0 is fully dilated
+    pub pupil_dilation: f32,
+    // Smoothed look direction, length at most 1.0
+    pub gaze: Vector2<f32>,
+    // Small jump layered on top of the gaze, replaced at every saccade
+    pub saccade: Vector2<f32>,
+    blink_timer: f32,
+    next_blink_in: f32,
+    next_saccade_in: f32,
+}
+
+impl EyeAnimation {
+    pub fn new(blink_rate: f32) -> Self {
+        EyeAnimation {
+            blink_rate,
+            openness: 1.0,
+            pupil_dilation: 0.0,
+            gaze: Vector2 { x: 0.0, y: 0.0 },
+            saccade: Vector2 { x: 0.0, y: 0.0 },
+            blink_timer: 0.0,
+            next_blink_in: 1.0 / blink_rate.max(0.001),
+            next_saccade_in: MAX_SACCADE_INTERVAL,
+        }
+    }
+
+    pub fn is_blinking(&self) -> bool {
+        self.blink_timer > 0.0
+    }
+
+    // `look_direction` is where the eye wants to look (length at most 1.0) and
+    // `arousal` in 0.0..=1.0 drives pupil dilation.
+    pub fn update<R: Rng + ?Sized>(
+        &mut self,
+        dt: f32,
+        look_direction: Vector2<f32>,
+        arousal: f32,
+        rng: &mut R,
+    ) {
+        self.update_blink(dt, rng);
+        self.update_saccade(dt, rng);
+
+        let gaze_step = 1.0 - (-GAZE_SMOOTHING * dt).exp();
+        self.gaze.x += (look_direction.x - self.gaze.x) * gaze_step;
+        self.gaze.y += (look_direction.y - self.gaze.y) * gaze_step;
+
+        let pupil_step = 1.0 - (-PUPIL_SMOOTHING * dt).exp();
+        self.pupil_dilation += (arousal.clamp(0.0, 1.0) - self.pupil_dilation) * pupil_step;
+    }
+
+    fn update_blink<R: Rng + ?Sized>(&mut self, dt: f32, rng: &mut R) {
+        if self.is_blinking() {
+            self.blink_timer = (self.blink_timer - dt).max(0.0);
+        } else {
+            self.next_blink_in -= dt;
+            if self.next_blink_in <= 0.0 && self.blink_rate > 0.0 {
+                self.blink_timer = BLINK_DURATION;
+                // Exponential gaps make blinks feel random rather than metronomic
+                let u: f32 = rng.gen_range(0.001..1.0);
+                self.next_blink_in = -u.ln() / self.blink_rate;
+            }
+        }
+
+        // Lid closes over the first half of the blink and opens over the second
+        let phase = 1.0 - self.blink_timer / BLINK_DURATION;
+        self.openness = if self.is_blinking() {
+            (1.0 - 2.0 * phase).abs()
+        } else {
+            1.0
+        };
+    }
+
+    fn update_saccade<R: Rng + ?Sized>(&mut self, dt: f32, rng: &mut R) {
+        self.next_saccade_in -= dt;
+        if self.next_saccade_in <= 0.0 {
+            self.saccade = Vector2 {
+                x: rng.gen_range(-SACCADE_SIZE..SACCADE_SIZE),
+                y: rng.gen_range(-SACCADE_SIZE..SACCADE_SIZE),
+            };
+            self.next_saccade_in = rng.gen_range(MIN_SACCADE_INTERVAL..MAX_SACCADE_INTERVAL);
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Eye {
     position: Point2<f32>,
     scale: f32,
     color: Color,
     in_proximity: bool,
-    distance_from_player: f32
+    distance_from_player: f32,
+    // The eye is only drawn while the player is between these distances
+    visible_from: f32,
+    visible_to: f32,
+    // How far the eye drifts from its anchor when looking all the way to one side
+    gaze_travel: f32,
+    // Extra excitement on top of proximity, e.g. from gameplay events
+    pub mood: f32,
+    pub animation: EyeAnimation,
+    // Each eye blinks and darts from its own stream so a seed replays them exactly
+    rng: ChaCha8Rng,
 }
 
 impl Eye {
-    pub fn new(x: f32, y: f32, scale: f32) -> Self {
+    pub fn new(x: f32, y: f32, scale: f32, blink_rate: f32, rng: ChaCha8Rng) -> Self {
         Eye {
             position: Point2 { x, y },
             scale,
             color: Color::WHITE,
             in_proximity: false,
             distance_from_player: 0.0,
+            visible_from: 300.0,
+            visible_to: 600.0,
+            gaze_travel: scale * GAZE_TRAVEL,
+            mood: 0.0,
+            animation: EyeAnimation::new(blink_rate),
+            rng,
         }
     }
     // Draw the eye on the screen
     pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
 
-        if self.distance_from_player < 600.0 && self.distance_from_player > 300.0{
+        if self.distance_from_player < self.visible_to && self.distance_from_player > self.visible_from {
+            // Blinking squashes the whole eye vertically
+            let lid = graphics::DrawParam::default()
+                .dest(self.position)
+                .scale([1.0, self.animation.openness.max(0.05)]);
+
             let circle_mesh = MeshBuilder::new()
                 .circle(
                     graphics::DrawMode::fill(),
-                    [self.position.x, self.position.y],
+                    [0.0, 0.0],
                     self.scale,
                     0.2,
                     self.color,
                 )?
                 .build(ctx)?;
-    
-            graphics::draw(ctx, &circle_mesh, graphics::DrawParam::default())?;
-    
-            let pupil_scale = self.scale * 0.3;
+
+            graphics::draw(ctx, &circle_mesh, lid)?;
+
+            if self.animation.openness < 0.3 {
+                return Ok(());
+            }
+
+            let pupil_scale = self.scale
+                * (MIN_PUPIL_SIZE + (MAX_PUPIL_SIZE - MIN_PUPIL_SIZE) * self.animation.pupil_dilation);
             let pupil_color = Color::BLACK;
-    
+
+            // The pupil wanders inside the white with the saccades
+            let pupil_travel = (self.scale - pupil_scale).max(0.0);
+            let pupil_offset = [
+                (self.animation.gaze.x + self.animation.saccade.x) * pupil_travel * 0.5,
+                (self.animation.gaze.y + self.animation.saccade.y) * pupil_travel * 0.5,
+            ];
+
             let pupil_mesh = MeshBuilder::new()
                 .circle(
                     graphics::DrawMode::fill(),
-                    [self.position.x, self.position.y],
+                    pupil_offset,
                     pupil_scale,
                     0.1,
                     pupil_color,
                 )?
                 .build(ctx)?;
-    
-            graphics::draw(ctx, &pupil_mesh, graphics::DrawParam::default())
+
+            graphics::draw(ctx, &pupil_mesh, lid)
         } else {
             Ok(()) // Explicitly return Ok(()) if nothing should be drawn
         }
     }
     pub fn update(
         &mut self,
+        dt: f32,
         player_position: Point2<f32>,
         collectible_center: Point2<f32>,
         distance_from_player: f32,
@@ -80,13 +219,23 @@ impl Eye {
                 Vector2 { x: 0.0, y: 0.0 } // No movement if player is exactly at the collectible center
             };
 
-            // Define the maximum offset the eye can have from the center
-            let max_eye_offset = self.scale * 4.0; // for instance, 30% of eye's scale, adjust as needed
+            // The closer the player, the wider the pupil
+            let proximity_arousal = if in_proximity {
+                1.0 - (distance_from_player / self.visible_to).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            self.animation.update(
+                dt,
+                direction,
+                proximity_arousal.max(self.mood),
+                &mut self.rng,
+            );
 
-            // Calculate the actual offset
+            // Calculate the actual offset, following the smoothed gaze rather than snapping
             let eye_offset = Vector2 {
-                x: direction.x * max_eye_offset,
-                y: direction.y * max_eye_offset,
+                x: self.animation.gaze.x * self.gaze_travel,
+                y: self.animation.gaze.y * self.gaze_travel,
             };
 
             // Update eye's position to be a slight offset from the collectible's center
@@ -106,4 +255,78 @@ impl Eye {
     pub fn set_color(&mut self, new_color: Color) {
         self.color = new_color;
     }
+
+    pub fn set_visible_range(&mut self, visible_from: f32, visible_to: f32) {
+        self.visible_from = visible_from;
+        self.visible_to = visible_to;
+    }
+
+    pub fn set_gaze_travel(&mut self, gaze_travel: f32) {
+        self.gaze_travel = gaze_travel;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    const DT: f32 = 0.01;
+    const AHEAD: Vector2<f32> = Vector2 { x: 0.0, y: 0.0 };
+
+    #[test]
+    fn lid_closes_once_the_next_blink_is_due() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut animation = EyeAnimation::new(1.0);
+        let mut elapsed = 0.0;
+        while !animation.is_blinking() {
+            animation.update(DT, AHEAD, 0.0, &mut rng);
+            elapsed += DT;
+            assert!(elapsed < 2.0, "never blinked");
+        }
+        // The first blink is due a second in at one blink per second
+        assert!(elapsed >= 1.0 - DT);
+
+        animation.update(BLINK_DURATION / 4.0, AHEAD, 0.0, &mut rng);
+        assert!(animation.is_blinking());
+        assert!(animation.openness < 1.0);
+
+        animation.update(BLINK_DURATION, AHEAD, 0.0, &mut rng);
+        assert!(!animation.is_blinking());
+        assert_eq!(animation.openness, 1.0);
+    }
+
+    #[test]
+    fn gaze_eases_toward_the_look_direction() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut animation = EyeAnimation::new(DEFAULT_BLINK_RATE);
+        let right = Vector2 { x: 1.0, y: 0.0 };
+
+        animation.update(0.1, right, 0.0, &mut rng);
+        let expected = 1.0 - (-GAZE_SMOOTHING * 0.1).exp();
+        assert!((animation.gaze.x - expected).abs() < 1e-5);
+        assert!(animation.gaze.x < 1.0);
+
+        for _ in 0..100 {
+            animation.update(DT, right, 0.0, &mut rng);
+        }
+        assert!((animation.gaze.x - 1.0).abs() < 1e-3);
+        assert!(animation.gaze.y.abs() < 1e-3);
+    }
+
+    #[test]
+    fn pupils_widen_with_arousal() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut calm = EyeAnimation::new(DEFAULT_BLINK_RATE);
+        let mut excited = EyeAnimation::new(DEFAULT_BLINK_RATE);
+        let mut previous = 0.0;
+        for _ in 0..100 {
+            calm.update(DT, AHEAD, 0.2, &mut rng);
+            excited.update(DT, AHEAD, 0.9, &mut rng);
+            assert!(excited.pupil_dilation > previous);
+            previous = excited.pupil_dilation;
+        }
+        assert!(excited.pupil_dilation > calm.pupil_dilation);
+        assert!(excited.pupil_dilation <= 0.9);
+    }
 }
@@ -0,0 +1,262 @@
+use crate::eye::{Eye, DEFAULT_BLINK_RATE, GAZE_TRAVEL};
+use crate::world_seed::{WorldSeed, EYE_STREAM};
+use ggez::{Context, GameResult};
+use mint::{Point2, Vector2};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_EYE_SIZE: f32 = 0.1;
+// Eyes stay inside this fraction of the collectible's radius, gaze included,
+// so the wobbling outline doesn't leave them hanging over the edge
+pub const EYE_INSET: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EyePlacement {
+    // Spread around the rim of the blob
+    Outline,
+    // Inside the body; a single eye sits dead center
+    Interior,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GazeMode {
+    // Every eye blinks and darts on its own
+    Independent,
+    // All eyes share the first eye's animation
+    Synchronized,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EyeLayout {
+    pub count: usize,
+    pub placement: EyePlacement,
+    // Eye sizes as a fraction of the collectible's size, reused in order if there are fewer than `count`
+    pub sizes: Vec<f32>,
+    pub gaze: GazeMode,
+    // Average blinks per second; synchronized eyes all blink with the first one
+    pub blink_rate: f32,
+    // Eyes are only drawn while the player is between these distances
+    pub visible_from: f32,
+    pub visible_to: f32,
+}
+
+impl Default for EyeLayout {
+    fn default() -> Self {
+        EyeLayout {
+            count: 1,
+            placement: EyePlacement::Interior,
+            sizes: vec![DEFAULT_EYE_SIZE],
+            gaze: GazeMode::Synchronized,
+            blink_rate: DEFAULT_BLINK_RATE,
+            visible_from: 300.0,
+            visible_to: 600.0,
+        }
+    }
+}
+
+impl EyeLayout {
+    // Offsets of each eye from the collectible's center
+    pub fn anchors(&self, collectible_size: f32) -> Vec<Vector2<f32>> {
+        (0..self.count)
+            .map(|i| {
+                let radius = self.anchor_distance(i, collectible_size);
+                // Start at the top so a pair of eyes sits side by side
+                let angle = -std::f32::consts::FRAC_PI_2
+                    + (i as f32 + 0.5) * std::f32::consts::TAU / self.count as f32;
+                Vector2 {
+                    x: angle.cos() * radius,
+                    y: angle.sin() * radius,
+                }
+            })
+            .collect()
+    }
+
+    // Eye radius as a fraction of the collectible's size
+    pub fn size_of(&self, index: usize) -> f32 {
+        if self.sizes.is_empty() {
+            DEFAULT_EYE_SIZE
+        } else {
+            self.sizes[index % self.sizes.len()]
+        }
+    }
+
+    // How far eye `index` may drift from its anchor towards the player without leaving the body
+    pub fn gaze_travel(&self, index: usize, collectible_size: f32) -> f32 {
+        let eye_radius = collectible_size * self.size_of(index);
+        let room = self.inner_radius(collectible_size) - self.anchor_distance(index, collectible_size) - eye_radius;
+        room.clamp(0.0, eye_radius * GAZE_TRAVEL)
+    }
+
+    fn inner_radius(&self, collectible_size: f32) -> f32 {
+        collectible_size / 2.0 * EYE_INSET
+    }
+
+    fn anchor_distance(&self, index: usize, collectible_size: f32) -> f32 {
+        match (self.placement, self.count) {
+            (EyePlacement::Interior, 1) => 0.0,
+            (EyePlacement::Interior, _) => collectible_size / 2.0 * 0.4,
+            // Just inside the rim, however big the eye is
+            (EyePlacement::Outline, _) => {
+                (self.inner_radius(collectible_size) - collectible_size * self.size_of(index)).max(0.0)
+            }
+        }
+    }
+}
+
+// A collectible's set of eyes, laid out and animated according to an `EyeLayout`
+#[derive(Debug)]
+pub struct EyeCluster {
+    pub layout: EyeLayout,
+    pub eyes: Vec<Eye>,
+    anchors: Vec<Vector2<f32>>,
+}
+
+impl EyeCluster {
+    // Every eye draws its blinks and saccades from its own stream of `seed`
+    pub fn new(position: Point2<f32>, collectible_size: f32, layout: EyeLayout, seed: WorldSeed) -> Self {
+        let anchors = layout.anchors(collectible_size);
+        let eyes = anchors
+            .iter()
+            .enumerate()
+            .map(|(i, anchor)| {
+                let mut eye = Eye::new(
+                    position.x + anchor.x,
+                    position.y + anchor.y,
+                    collectible_size * layout.size_of(i),
+                    layout.blink_rate,
+                    seed.stream(&format!("{}{}", EYE_STREAM, i)),
+                );
+                eye.set_visible_range(layout.visible_from, layout.visible_to);
+                eye.set_gaze_travel(layout.gaze_travel(i, collectible_size));
+                eye
+            })
+            .collect();
+        EyeCluster {
+            layout,
+            eyes,
+            anchors,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        dt: f32,
+        player_position: Point2<f32>,
+        collectible_center: Point2<f32>,
+        distance_from_player: f32,
+        in_proximity: bool,
+    ) {
+        for (i, eye) in self.eyes.iter_mut().enumerate() {
+            let anchor = Point2 {
+                x: collectible_center.x + self.anchors[i].x,
+                y: collectible_center.y + self.anchors[i].y,
+            };
+            if i > 0 && self.layout.gaze == GazeMode::Synchronized {
+                // Follow the leader instead of rolling our own blinks and saccades
+                eye.update(0.0, player_position, anchor, distance_from_player, in_proximity);
+            } else {
+                eye.update(dt, player_position, anchor, distance_from_player, in_proximity);
+            }
+        }
+
+        if self.layout.gaze == GazeMode::Synchronized {
+            if let Some((leader, followers)) = self.eyes.split_first_mut() {
+                for eye in followers {
+                    eye.animation = leader.animation.clone();
+                }
+            }
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        for eye in &self.eyes {
+            eye.draw(ctx)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: f32 = 100.0;
+
+    fn layout(count: usize, placement: EyePlacement, sizes: Vec<f32>) -> EyeLayout {
+        EyeLayout {
+            count,
+            placement,
+            sizes,
+            ..EyeLayout::default()
+        }
+    }
+
+    fn length(vector: &Vector2<f32>) -> f32 {
+        (vector.x * vector.x + vector.y * vector.y).sqrt()
+    }
+
+    #[test]
+    fn a_single_interior_eye_sits_in_the_middle() {
+        let anchors = EyeLayout::default().anchors(SIZE);
+        assert_eq!(anchors.len(), 1);
+        assert_eq!(length(&anchors[0]), 0.0);
+    }
+
+    #[test]
+    fn outline_eyes_sit_further_out_than_interior_ones() {
+        let interior = layout(4, EyePlacement::Interior, vec![0.1]).anchors(SIZE);
+        let outline = layout(4, EyePlacement::Outline, vec![0.1]).anchors(SIZE);
+        for (inner, outer) in interior.iter().zip(&outline) {
+            assert!(length(outer) > length(inner));
+        }
+        // A pair starts at the top and sits side by side
+        let pair = layout(2, EyePlacement::Interior, vec![0.1]).anchors(SIZE);
+        assert!((pair[0].y - pair[1].y).abs() < 1e-4);
+        assert!(pair[0].x > 0.0 && pair[1].x < 0.0);
+    }
+
+    #[test]
+    fn eyes_stay_inside_the_body_wherever_they_look() {
+        for placement in [EyePlacement::Interior, EyePlacement::Outline] {
+            for count in 1..=8 {
+                let layout = layout(count, placement, vec![0.05, 0.1, 0.25]);
+                for (i, anchor) in layout.anchors(SIZE).iter().enumerate() {
+                    let reach = length(anchor) + layout.gaze_travel(i, SIZE) + SIZE * layout.size_of(i);
+                    assert!(reach <= SIZE / 2.0 * EYE_INSET + 1e-3, "{:?} x{} eye {}", placement, count, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn eyes_replay_from_the_collectible_seed() {
+        let run = |seed: u64| {
+            let layout = EyeLayout {
+                gaze: GazeMode::Independent,
+                blink_rate: 2.0,
+                ..layout(2, EyePlacement::Interior, vec![0.1])
+            };
+            let mut cluster = EyeCluster::new(Point2 { x: 0.0, y: 0.0 }, SIZE, layout, WorldSeed(seed));
+            let mut saccades = Vec::new();
+            for _ in 0..300 {
+                cluster.update(0.02, Point2 { x: 100.0, y: 0.0 }, Point2 { x: 0.0, y: 0.0 }, 400.0, true);
+                saccades.push([cluster.eyes[0].animation.saccade.x, cluster.eyes[1].animation.saccade.x]);
+            }
+            saccades
+        };
+        let first = run(11);
+        assert_eq!(first, run(11));
+        assert_ne!(first, run(12));
+        // Independent eyes each draw from a stream of their own
+        assert!(first.iter().any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn sizes_repeat_in_order() {
+        let layout = layout(5, EyePlacement::Interior, vec![0.05, 0.1]);
+        let sizes: Vec<f32> = (0..5).map(|i| layout.size_of(i)).collect();
+        assert_eq!(sizes, vec![0.05, 0.1, 0.05, 0.1, 0.05]);
+        assert_eq!(self::layout(1, EyePlacement::Interior, Vec::new()).size_of(3), DEFAULT_EYE_SIZE);
+    }
+}
@@ -0,0 +1,114 @@
+use mint::Point2;
+
+// Number of samples taken along the parametric curve before resampling
+const HEART_CURVE_SEGMENTS: usize = 100;
+
+// Points of the heart's curve, centered on the origin.
+pub fn heart_curve(size: f32) -> Vec<Point2<f32>> {
+    let mut points: Vec<Point2<f32>> = Vec::with_capacity(HEART_CURVE_SEGMENTS);
+
+    // Generate the points of a heart shape
+    for i in 0..HEART_CURVE_SEGMENTS {
+        let t = i as f32 / HEART_CURVE_SEGMENTS as f32 * 2.0 * std::f32::consts::PI;
+        // Parametric equations for the heart shape
+        let x = 16.0 * (0.75 * t.sin()).powi(3);
+        let y = 13.0 * t.cos() - 5.0 * (2.0 * t).cos() - 2.0 * (3.0 * t).cos() - (4.0 * t).cos();
+
+        points.push(Point2 {
+            x: (size / 10.0) * x,
+            y: -(size / 25.0) * y,
+        });
+    }
+
+    // Move the middle of the bounding box onto the origin so it shares a center with the blob
+    let min_x = points.iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+    let max_x = points.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max);
+    let min_y = points.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+    let max_y = points.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max);
+    let center = Point2 {
+        x: (min_x + max_x) / 2.0,
+        y: (min_y + max_y) / 2.0,
+    };
+    points
+        .iter()
+        .map(|p| Point2 {
+            x: p.x - center.x,
+            y: p.y - center.y,
+        })
+        .collect()
+}
+
+// The heart outline resampled to `num_points` points at the same evenly spaced angles
+// the blob outline uses, so point `i` of one lines up with point `i` of the other.
+pub fn heart_outline(size: f32, num_points: usize) -> Vec<Point2<f32>> {
+    let curve = heart_curve(size);
+    let angle_step = (2.0 * std::f32::consts::PI) / num_points as f32;
+
+    (0..num_points)
+        .map(|i| {
+            let angle = i as f32 * angle_step;
+            let (dx, dy) = (angle.cos(), angle.sin());
+            // Cast a ray from the center and keep the furthest crossing with the curve
+            let mut furthest = 0.0_f32;
+            for j in 0..curve.len() {
+                let a = curve[j];
+                let b = curve[(j + 1) % curve.len()];
+                if let Some(distance) = ray_segment_distance(dx, dy, a, b) {
+                    furthest = furthest.max(distance);
+                }
+            }
+            Point2 {
+                x: dx * furthest,
+                y: dy * furthest,
+            }
+        })
+        .collect()
+}
+
+// Distance along the ray from the origin in direction (dx, dy) to segment a-b, if it hits
+fn ray_segment_distance(dx: f32, dy: f32, a: Point2<f32>, b: Point2<f32>) -> Option<f32> {
+    let (ex, ey) = (b.x - a.x, b.y - a.y);
+    let denominator = dx * ey - dy * ex;
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let distance = (a.x * ey - a.y * ex) / denominator;
+    let along_segment = (a.x * dy - a.y * dx) / denominator;
+    if distance >= 0.0 && (0.0..=1.0).contains(&along_segment) {
+        Some(distance)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_is_centered_on_the_origin() {
+        let curve = heart_curve(40.0);
+        let min_x = curve.iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+        let max_x = curve.iter().map(|p| p.x).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = curve.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+        let max_y = curve.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max);
+        assert!((min_x + max_x).abs() < 1e-3);
+        assert!((min_y + max_y).abs() < 1e-3);
+    }
+
+    #[test]
+    fn outline_has_one_point_per_blob_angle() {
+        for num_points in [3, 16, 25] {
+            let outline = heart_outline(40.0, num_points);
+            assert_eq!(outline.len(), num_points);
+            for (i, point) in outline.iter().enumerate() {
+                // Point `i` sits on the ray the blob uses for its own point `i`
+                let angle = i as f32 * 2.0 * std::f32::consts::PI / num_points as f32;
+                let distance = (point.x * point.x + point.y * point.y).sqrt();
+                assert!(distance > 0.0, "point {} collapsed onto the center", i);
+                assert!((point.x - angle.cos() * distance).abs() < 1e-3);
+                assert!((point.y - angle.sin() * distance).abs() < 1e-3);
+            }
+        }
+    }
+}
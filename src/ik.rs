@@ -0,0 +1,154 @@
+// FABRIK inverse kinematics for chains of equal-length segments.
+// Pure vector math so it can be exercised without a graphics context.
+use nalgebra::Vector2;
+
+pub const DEFAULT_IK_ITERATIONS: usize = 10;
+pub const DEFAULT_IK_TOLERANCE: f32 = 0.5;
+
+// Moves `joints` so the last one reaches as close to `target` as the chain allows.
+// `joints[0]` is the fixed root. Each joint may bend at most `max_joint_angle` radians
+// away from the segment before it; the first segment is measured against `root_direction`.
+// Returns the distance left between the tip and the target.
+pub fn solve_fabrik(
+    joints: &mut [Vector2<f32>],
+    segment_length: f32,
+    target: Vector2<f32>,
+    root_direction: Vector2<f32>,
+    max_joint_angle: f32,
+    iterations: usize,
+    tolerance: f32,
+) -> f32 {
+    let count = joints.len();
+    if count < 2 {
+        return joints.first().map_or(0.0, |root| (target - root).norm());
+    }
+    let root = joints[0];
+
+    for _ in 0..iterations {
+        // Forward pass: drag the chain from the tip onto the target
+        joints[count - 1] = target;
+        for i in (0..count - 1).rev() {
+            let direction = direction_or(joints[i] - joints[i + 1], -root_direction);
+            joints[i] = joints[i + 1] + direction * segment_length;
+        }
+
+        // Backward pass: re-anchor at the root, bending no further than the limit
+        joints[0] = root;
+        let mut previous_direction = direction_or(root_direction, Vector2::new(1.0, 0.0));
+        for i in 1..count {
+            let direction = direction_or(joints[i] - joints[i - 1], previous_direction);
+            let direction = clamp_angle(direction, previous_direction, max_joint_angle);
+            joints[i] = joints[i - 1] + direction * segment_length;
+            previous_direction = direction;
+        }
+
+        if (joints[count - 1] - target).norm() <= tolerance {
+            break;
+        }
+    }
+    (joints[count - 1] - target).norm()
+}
+
+// Rotates `direction` towards `reference` until the angle between them is at most `max_angle`.
+// Both are expected to be unit vectors.
+pub fn clamp_angle(direction: Vector2<f32>, reference: Vector2<f32>, max_angle: f32) -> Vector2<f32> {
+    let cross = reference.x * direction.y - reference.y * direction.x;
+    let dot = reference.x * direction.x + reference.y * direction.y;
+    let angle = cross.atan2(dot);
+    if angle.abs() <= max_angle {
+        return direction;
+    }
+    let limited = max_angle * angle.signum();
+    let (sin, cos) = limited.sin_cos();
+    Vector2::new(
+        reference.x * cos - reference.y * sin,
+        reference.x * sin + reference.y * cos,
+    )
+}
+
+fn direction_or(vector: Vector2<f32>, fallback: Vector2<f32>) -> Vector2<f32> {
+    let length = vector.norm();
+    if length > f32::EPSILON {
+        vector / length
+    } else {
+        fallback
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+    const SEGMENT: f32 = 10.0;
+
+    // A chain of `joints` points lying along the x axis from the origin
+    fn straight_chain(joints: usize) -> Vec<Vector2<f32>> {
+        (0..joints).map(|i| Vector2::new(i as f32 * SEGMENT, 0.0)).collect()
+    }
+
+    fn angle_between(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+        (a.x * b.y - a.y * b.x).atan2(a.x * b.x + a.y * b.y)
+    }
+
+    #[test]
+    fn reachable_target_is_reached() {
+        let mut joints = straight_chain(5);
+        let target = Vector2::new(20.0, 25.0);
+        let right = Vector2::new(1.0, 0.0);
+        let error = solve_fabrik(&mut joints, SEGMENT, target, right, PI, 20, DEFAULT_IK_TOLERANCE);
+        assert!(error <= DEFAULT_IK_TOLERANCE);
+        assert!((joints[4] - target).norm() <= DEFAULT_IK_TOLERANCE);
+        assert_eq!(joints[0], Vector2::new(0.0, 0.0));
+        for pair in joints.windows(2) {
+            assert!(((pair[1] - pair[0]).norm() - SEGMENT).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn out_of_reach_target_straightens_the_chain() {
+        let mut joints = straight_chain(4);
+        let target = Vector2::new(0.0, 100.0);
+        let up = Vector2::new(0.0, 1.0);
+        let error = solve_fabrik(&mut joints, SEGMENT, target, up, PI, DEFAULT_IK_ITERATIONS, DEFAULT_IK_TOLERANCE);
+        // The chain is 30 long, so the tip stops 70 short, pointing straight at the target
+        assert!((error - 70.0).abs() < 1e-3);
+        for (i, joint) in joints.iter().enumerate() {
+            assert!(joint.x.abs() < 1e-3);
+            assert!((joint.y - i as f32 * SEGMENT).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn joints_bend_no_further_than_the_limit() {
+        let mut joints = straight_chain(6);
+        // Directly behind the root, which a stiff chain can't fold back to
+        let target = Vector2::new(-20.0, 5.0);
+        let right = Vector2::new(1.0, 0.0);
+        let limit = FRAC_PI_4 / 2.0;
+        solve_fabrik(&mut joints, SEGMENT, target, right, limit, DEFAULT_IK_ITERATIONS, DEFAULT_IK_TOLERANCE);
+
+        let mut previous = right;
+        for pair in joints.windows(2) {
+            let direction = (pair[1] - pair[0]).normalize();
+            assert!(angle_between(previous, direction).abs() <= limit + 1e-4);
+            previous = direction;
+        }
+    }
+
+    #[test]
+    fn clamp_angle_keeps_small_turns_and_limits_large_ones() {
+        let reference = Vector2::new(1.0, 0.0);
+        let slight = Vector2::new(FRAC_PI_4.cos(), FRAC_PI_4.sin());
+        assert_eq!(clamp_angle(slight, reference, FRAC_PI_2), slight);
+
+        let up = Vector2::new(0.0, 1.0);
+        let clamped = clamp_angle(up, reference, FRAC_PI_4);
+        assert!((angle_between(reference, clamped) - FRAC_PI_4).abs() < 1e-5);
+
+        let down = Vector2::new(0.0, -1.0);
+        let clamped = clamp_angle(down, reference, FRAC_PI_4);
+        assert!((angle_between(reference, clamped) + FRAC_PI_4).abs() < 1e-5);
+        assert!((clamped.norm() - 1.0).abs() < 1e-5);
+    }
+}
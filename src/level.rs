@@ -0,0 +1,420 @@
+// Level files describe a complete play field, e.g. `resources/levels/meadow.json`.
+// Every file carries a `version` so older levels keep loading as the format grows.
+// Tiled maps (`*.tmj`) load as levels too, see `tiled_map`.
+use crate::archetype::ArchetypeParams;
+use crate::cluster_layout::{ClusterLayout, ClusterSpec};
+use crate::density_map::DensityMapParams;
+use crate::obstacle::Obstacle;
+use crate::placement_constraints::{ExclusionZone, DEFAULT_MIN_SPAWN_DISTANCE};
+use crate::resources;
+use crate::tiled_map;
+use ggez::{GameError, GameResult};
+use mint::Point2;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const LEVEL_FORMAT_VERSION: u32 = 1;
+pub const DEFAULT_LEVEL: &str = "resources/levels/meadow.json";
+pub const LEVEL_ARG: &str = "--level";
+pub const DEFAULT_PROXIMITY_THRESHOLD: f32 = 800.0;
+// For clusters placed without a count of their own, in the editor or in a Tiled map
+pub const COLLECTIBLES_PER_CLUSTER: i32 = 2; // added to the generated count for each cluster
+pub const DEFAULT_CLUSTER_SIZE: f32 = 200.0; // spread of clusters in a level that had none
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl From<Position> for Point2<f32> {
+    fn from(position: Position) -> Self {
+        Point2 {
+            x: position.x,
+            y: position.y,
+        }
+    }
+}
+
+// A collectible at a fixed spot. `archetype` names one of the level's archetypes;
+// without it the kind is picked at random like for generated collectibles.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Placement {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archetype: Option<String>,
+}
+
+impl From<&Placement> for Point2<f32> {
+    fn from(placement: &Placement) -> Self {
+        Point2 {
+            x: placement.x,
+            y: placement.y,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorldSize {
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Anchor {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// Parameters for scattering collectibles, either around the clusters of a named layout,
+// around clusters given right here, or over a noise density map. Exactly one of `layout`,
+// `clusters` and `density` must be given.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeneratorParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    // Same format as the clusters of a layout file; `x`/`y` are fractions of the world size
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clusters: Vec<ClusterSpec>,
+    pub count: i32,
+    #[serde(default)]
+    pub cluster_size: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub density: Option<DensityMapParams>,
+}
+
+// At least one of `generate` and `placements` must be given. With both, the placed
+// collectibles are there in addition to the generated ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CollectibleSettings {
+    pub size: f32,
+    #[serde(default)]
+    pub hit_points: Option<u32>,
+    #[serde(default = "default_proximity_threshold")]
+    pub proximity_threshold: f32,
+    // Generated collectibles keep their centers at least this far from the player spawn
+    #[serde(default = "default_min_spawn_distance")]
+    pub min_spawn_distance: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generate: Option<GeneratorParams>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub placements: Vec<Placement>,
+}
+
+fn default_proximity_threshold() -> f32 {
+    DEFAULT_PROXIMITY_THRESHOLD
+}
+
+fn default_min_spawn_distance() -> f32 {
+    DEFAULT_MIN_SPAWN_DISTANCE
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UiAnchors {
+    pub restart_button: Anchor,
+    pub seed_label: Position,
+}
+
+impl Default for UiAnchors {
+    fn default() -> Self {
+        UiAnchors {
+            restart_button: Anchor {
+                x: 1000.0,
+                y: 200.0,
+                width: 150.0,
+                height: 50.0,
+            },
+            seed_label: Position { x: 20.0, y: 20.0 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    pub version: u32,
+    pub name: String,
+    pub world: WorldSize,
+    pub player_spawn: Position,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+    // Extra areas generated collectibles stay out of; obstacles and UI are excluded anyway
+    #[serde(default)]
+    pub exclusion_zones: Vec<ExclusionZone>,
+    pub collectibles: CollectibleSettings,
+    // Kinds of collectible that placements can ask for by name, built on top of the default one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archetypes: Vec<ArchetypeParams>,
+    // Path to a Tiled map whose tile layers are drawn under the field
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    // Path to a wave table; without one the field stays empty once cleared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waves: Option<String>,
+    #[serde(default)]
+    pub ui: UiAnchors,
+}
+
+impl Level {
+    // The level given with `--level <path>`, or the default one from the resources directory
+    pub fn path_from_args() -> String {
+        let args: Vec<String> = std::env::args().collect();
+        args.iter()
+            .position(|arg| arg == LEVEL_ARG)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+            .unwrap_or_else(|| resources::resolve(DEFAULT_LEVEL).display().to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Level> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|ext| ext == tiled_map::MAP_EXTENSION) {
+            return tiled_map::import(path);
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| GameError::CustomError(format!("Can't read level {}: {}", path.display(), e)))?;
+        Level::parse(&text, &path.display().to_string())
+    }
+
+    // Writes the level as pretty-printed JSON; refuses to write a level that wouldn't load again
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        let path = path.as_ref();
+        self.validate()
+            .map_err(|e| GameError::CustomError(format!("Not saving level {}: {}", path.display(), e)))?;
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|e| GameError::CustomError(format!("Can't encode level {}: {}", path.display(), e)))?;
+        std::fs::write(path, text + "\n")
+            .map_err(|e| GameError::CustomError(format!("Can't write level {}: {}", path.display(), e)))
+    }
+
+    // `source` names where the text came from in error messages
+    pub fn parse(text: &str, source: &str) -> GameResult<Level> {
+        // Check the version on its own first, so a level from a newer build gets a clear
+        // message instead of complaints about fields this build doesn't know yet
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+        let versioned: Versioned = serde_json::from_str(text)
+            .map_err(|e| GameError::CustomError(format!("Invalid level {}: {}", source, e)))?;
+        if versioned.version == 0 || versioned.version > LEVEL_FORMAT_VERSION {
+            return Err(GameError::CustomError(format!(
+                "Level {} has format version {}, this build reads versions 1 to {}",
+                source, versioned.version, LEVEL_FORMAT_VERSION
+            )));
+        }
+
+        let level: Level = serde_json::from_str(text)
+            .map_err(|e| GameError::CustomError(format!("Invalid level {}: {}", source, e)))?;
+        level
+            .validate()
+            .map_err(|e| GameError::CustomError(format!("Invalid level {}: {}", source, e)))?;
+        Ok(level)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.world.width <= 0.0 || self.world.height <= 0.0 {
+            return Err(format!(
+                "world size must be positive, got {}x{}",
+                self.world.width, self.world.height
+            ));
+        }
+        if !self.in_world(self.player_spawn.into()) {
+            return Err(format!(
+                "player spawn ({}, {}) is outside the {}x{} world",
+                self.player_spawn.x, self.player_spawn.y, self.world.width, self.world.height
+            ));
+        }
+        for (i, obstacle) in self.obstacles.iter().enumerate() {
+            if obstacle.width <= 0.0 || obstacle.height <= 0.0 {
+                return Err(format!("obstacle {}: width and height must be positive", i));
+            }
+            if obstacle.contains(self.player_spawn.into()) {
+                return Err(format!("obstacle {} covers the player spawn", i));
+            }
+        }
+
+        for (i, zone) in self.exclusion_zones.iter().enumerate() {
+            let valid = match *zone {
+                ExclusionZone::Circle { radius, .. } => radius > 0.0,
+                ExclusionZone::Rect { width, height, .. } => width > 0.0 && height > 0.0,
+            };
+            if !valid {
+                return Err(format!("exclusion zone {}: size must be positive", i));
+            }
+        }
+
+        for (i, archetype) in self.archetypes.iter().enumerate() {
+            archetype.validate()?;
+            if self.archetypes[..i].iter().any(|other| other.name == archetype.name) {
+                return Err(format!("archetype '{}' is defined more than once", archetype.name));
+            }
+        }
+
+        let collectibles = &self.collectibles;
+        if collectibles.size <= 0.0 {
+            return Err(format!("collectible size must be positive, got {}", collectibles.size));
+        }
+        if collectibles.generate.is_none() && collectibles.placements.is_empty() {
+            return Err("collectibles: one of `generate` or `placements` is required".to_string());
+        }
+        // Waves refill the field by generating it again; placements alone never change
+        if collectibles.generate.is_none() && self.waves.is_some() {
+            return Err("waves need `collectibles.generate`; a level with only placements can't have waves".to_string());
+        }
+        if let Some(generator) = &collectibles.generate {
+            if generator.count < 0 {
+                return Err(format!("collectibles: count must be 0 or more, got {}", generator.count));
+            }
+            let sources = [
+                generator.layout.is_some(),
+                !generator.clusters.is_empty(),
+                generator.density.is_some(),
+            ];
+            match sources.iter().filter(|&&given| given).count() {
+                0 => return Err("collectibles: one of `layout`, `clusters` or `density` is required".to_string()),
+                1 => {}
+                _ => return Err("collectibles: give only one of `layout`, `clusters` and `density`".to_string()),
+            }
+            if let Some(density) = &generator.density {
+                density.validate().map_err(|e| format!("collectibles: {}", e))?;
+            }
+            if !generator.clusters.is_empty() {
+                self.inline_layout()
+                    .validate()
+                    .map_err(|e| format!("collectibles: {}", e))?;
+            }
+            if generator.cluster_size < 0.0 {
+                return Err(format!(
+                    "collectibles: cluster_size must be 0 or more, got {}",
+                    generator.cluster_size
+                ));
+            }
+        }
+        for (i, placement) in collectibles.placements.iter().enumerate() {
+            if !self.in_world(placement.into()) {
+                return Err(format!(
+                    "collectibles: placement {} at ({}, {}) is outside the world",
+                    i, placement.x, placement.y
+                ));
+            }
+            if let Some(name) = &placement.archetype {
+                if self.archetype(name).is_none() {
+                    return Err(format!("collectibles: placement {} uses unknown archetype '{}'", i, name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The clusters given in the level itself as a layout named after the level; it has no
+    // clusters if the level uses a layout file or a density map instead
+    pub fn inline_layout(&self) -> ClusterLayout {
+        ClusterLayout {
+            name: self.name.clone(),
+            clusters: self
+                .collectibles
+                .generate
+                .as_ref()
+                .map_or_else(Vec::new, |generator| generator.clusters.clone()),
+        }
+    }
+
+    pub fn archetype(&self, name: &str) -> Option<&ArchetypeParams> {
+        self.archetypes.iter().find(|archetype| archetype.name == name)
+    }
+
+    fn in_world(&self, position: Point2<f32>) -> bool {
+        (0.0..=self.world.width).contains(&position.x) && (0.0..=self.world.height).contains(&position.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEADOW: &str = r#"{
+        "version": 1,
+        "name": "meadow",
+        "world": { "width": 2000, "height": 2000 },
+        "player_spawn": { "x": 100, "y": 100 },
+        "obstacles": [],
+        "collectibles": {
+            "size": 100,
+            "generate": { "layout": "rows", "count": 2, "cluster_size": 300 }
+        },
+        "waves": "resources/waves.json"
+    }"#;
+
+    fn parse_error(text: &str) -> String {
+        match Level::parse(text, "level.json") {
+            Err(GameError::CustomError(message)) => message,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn waves_need_a_generated_field() {
+        assert!(Level::parse(MEADOW, "level.json").is_ok());
+        let placements_only = MEADOW.replace(
+            r#""generate": { "layout": "rows", "count": 2, "cluster_size": 300 }"#,
+            r#""placements": [ { "x": 500, "y": 500 } ]"#,
+        );
+        assert!(parse_error(&placements_only).contains("a level with only placements can't have waves"));
+    }
+
+    #[test]
+    fn shipped_levels_load() {
+        let dir = resources::resolve("resources/levels");
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(e) = Level::load(&path) {
+                panic!("{}: {:?}", path.display(), e);
+            }
+        }
+    }
+
+    #[test]
+    fn saved_text_parses_back_to_the_same_level() {
+        let level = Level::parse(
+            r#"{
+                "version": 1,
+                "name": "round trip",
+                "world": { "width": 1600, "height": 1200 },
+                "player_spawn": { "x": 800, "y": 600 },
+                "obstacles": [ { "x": 100, "y": 100, "width": 50, "height": 80 } ],
+                "exclusion_zones": [ { "shape": "circle", "x": 1400, "y": 200, "radius": 90 } ],
+                "archetypes": [ { "name": "brute", "hit_points": 5, "magnet_bonus": 40 } ],
+                "collectibles": {
+                    "size": 70,
+                    "generate": {
+                        "count": 6,
+                        "cluster_size": 150,
+                        "clusters": [
+                            { "x": 0.2, "y": 0.3, "spread": 120, "count": 2 },
+                            { "x": 0.7, "y": 0.8, "weight": 2 }
+                        ]
+                    },
+                    "placements": [ { "x": 300, "y": 900 }, { "x": 1200, "y": 900, "archetype": "brute" } ]
+                },
+                "waves": "resources/waves.json"
+            }"#,
+            "level.json",
+        )
+        .unwrap();
+        let text = serde_json::to_string_pretty(&level).unwrap();
+        assert_eq!(Level::parse(&text, "saved.json").unwrap(), level);
+        assert_eq!(level.collectibles.placements[1].archetype.as_deref(), Some("brute"));
+        assert_eq!(level.collectibles.generate.as_ref().unwrap().clusters.len(), 2);
+    }
+}
@@ -1,32 +1,75 @@
 mod player;
 mod main_state;
 mod collectible;
+mod archetype;
+mod personality;
 mod collectible_placement;
+mod density_map;
+mod poisson_disk;
+mod placement_constraints;
+mod progression;
 mod button;
-mod collectible_cluster_points;
+mod cluster_layout;
 mod smoke_effect;
+mod editor;
+mod effects;
 mod amorphous_mesh_creator;
+mod metaball;
+mod soft_body;
 mod proximity_and_collision_handler;
 mod event_handler;
 mod collidable;
+mod level;
+mod tiled_map;
+mod obstacle;
 mod tentacle;
+mod ik;
+mod behavior;
 mod eye;
-mod heart_mesh;
+mod eye_cluster;
+mod heart_outline;
 mod collectibles;
 mod utils;
+mod world_seed;
+mod resources;
 use ggez::{conf, event, ContextBuilder};
+use level::Level;
 use main_state::MainState;
+use world_seed::WorldSeed;
 const AUTHOR: &str = "badboyrenegade";
 const GAME_ID: &str = "top down";
-const SCREEN_WIDTH: f32 = 2000.0;
-const SCREEN_HEIGHT: f32 = 2000.0;
 
 fn main() -> ggez::GameResult {
-    
+    // Pass `--level <path>` to play a different level file or Tiled map (`*.tmj`)
+    let level_path = Level::path_from_args();
+    let level = Level::load(&level_path)?;
+    // Pass `--seed <value>` (or set TOP_SEED) to replay a world
+    let seed = WorldSeed::from_args_or_env();
+    println!("world seed: {}", seed);
+
+    // `--preview-density` prints the level's density map for this seed instead of playing
+    if std::env::args().any(|arg| arg == density_map::PREVIEW_ARG) {
+        return preview_density(&level, &seed);
+    }
+
     let (mut ctx, event_loop) = ContextBuilder::new(GAME_ID, AUTHOR)
         .window_setup(conf::WindowSetup::default().title(GAME_ID))
-        .window_mode(conf::WindowMode::default().dimensions(SCREEN_WIDTH, SCREEN_HEIGHT))
+        .window_mode(conf::WindowMode::default().dimensions(level.world.width, level.world.height))
         .build()?;
-    let state = MainState::new(&mut ctx, SCREEN_WIDTH, SCREEN_HEIGHT)?;
+    let state = MainState::from_level(&mut ctx, level, level_path, seed)?;
     event::run(ctx, event_loop, state)
 }
+
+fn preview_density(level: &Level, seed: &WorldSeed) -> ggez::GameResult {
+    let params = level
+        .collectibles
+        .generate
+        .as_ref()
+        .and_then(|generator| generator.density.as_ref())
+        .ok_or_else(|| ggez::GameError::CustomError(format!("Level {} has no density map", level.name)))?;
+    let grid = density_map::preview(params, seed, level.world.width, level.world.height);
+    let (_, patches) = grid.regions();
+    print!("{}", grid.to_ascii());
+    println!("{} patches above threshold {}", patches, params.threshold);
+    Ok(())
+}
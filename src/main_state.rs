@@ -1,95 +1,333 @@
+use crate::archetype::Archetype;
 use crate::button::Button;
-use crate::collectibles::Collectibles;
+use crate::cluster_layout::{self, ClusterLayout};
+use crate::collectible_placement::{FieldSettings, FixedPlacement, PlacementReport};
+use crate::collectibles::{Collectibles, SpawnSource};
+use crate::editor::{self, Editor, EditorCommand, Tool};
 use crate::event_handler::EventHandler;
+use crate::level::{Anchor, Level};
+use crate::placement_constraints::PlacementConstraints;
 use crate::player::Player;
-use crate::smoke_effect::SmokeEffect;
-use ggez::{event, graphics, Context, GameResult};
+use crate::progression::{Progression, WaveTable};
+use crate::resources;
+use crate::effects::Effects;
+use crate::tiled_map::{self, TileBackground};
+use crate::world_seed::WorldSeed;
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use ggez::{event, graphics, Context, GameError, GameResult};
 use gilrs::Gilrs;
 use mint::{Point2, Vector2};
 use rand::Rng;
-const COLLECTIBLE_SIZE: f32 = 100.0;
-const COLLECTIBLE_COUNT: i32 = 2;
-const CLUSTER_SIZE: f32 = 300.0;
+use std::path::Path;
 const PARTICLES_IN_SMOKE: i32 = 10;
-const PLAYER_TO_COLLECTIBLE_PROXIMITY_THRESHOLD: f32 = 800.0;
-pub const PLAYER_POSITION_X: f32 = 100.0;
-pub const PLAYER_POSITION_Y: f32 = 100.0;
 pub struct MainState {
     event_handler: EventHandler,
     player: Player,
     // collectibles: Vec<Collectible>,
-    smoke_effect_pool: Vec<SmokeEffect>,
+    effects: Effects,
     restart_button: Button,
-    default_player_position: Point2<f32>,
     collectibles: Collectibles,
+    seed: WorldSeed,
+    // Shown on screen so a layout can be reported and reproduced
+    seed_label: graphics::Text,
+    // Read again on restart, so edits to the file show up without relaunching
+    level_path: String,
+    level: Level,
+    // `None` if the level has no wave table
+    progression: Option<Progression>,
+    // Tile layers of the level's Tiled map, if it has one
+    background: Option<TileBackground>,
+    // The game is paused while this is active
+    editor: Editor,
 }
 
 impl MainState {
     fn reset_player_position(&mut self) {
-        self.player.position = self.default_player_position;
+        self.player.position = self.player.default_position;
     }
-    pub fn new(ctx: &mut Context, screen_width: f32, screen_height: f32) -> GameResult<MainState> {
+    pub fn from_level(ctx: &mut Context, level: Level, level_path: String, seed: WorldSeed) -> GameResult<MainState> {
+        let progression = MainState::load_progression(&level)?;
         //gamepad
-        let collectibles = Collectibles::new(
-            ctx,
-            screen_width,
-            screen_height,
-            COLLECTIBLE_COUNT,
-            PLAYER_TO_COLLECTIBLE_PROXIMITY_THRESHOLD,
-            CLUSTER_SIZE,
-            COLLECTIBLE_SIZE,
-        )?; // Adjust parameters as needed
+        let collectibles = MainState::build_collectibles(&level, progression.as_ref(), seed)?;
+        MainState::log_placement(&collectibles.placement_report);
 
         let gilrs = Gilrs::new().unwrap();
         //gamepad events
         let event_handler = EventHandler::new(gilrs);
-        let default_player_position = Point2 {
-            x: PLAYER_POSITION_X,
-            y: PLAYER_POSITION_Y,
+
+        let restart_button = MainState::build_restart_button(ctx, level.ui.restart_button)?;
+
+        //Initialize multiple smoke effects and put them into a pool
+        let effects = Effects::new(PARTICLES_IN_SMOKE, &seed);
+        let seed_label = MainState::build_seed_label(&level, progression.as_ref(), seed);
+
+        let player = Player::new(level.player_spawn.into());
+        let editor = Editor::new(ctx, &level)?;
+        let background = MainState::build_background(ctx, &level)?;
+
+        Ok(MainState {
+            event_handler,
+            player,
+            collectibles,
+            effects,
+            restart_button,
+            seed,
+            seed_label,
+            level_path,
+            level,
+            progression,
+            background,
+            editor,
+        })
+    }
+
+    fn load_progression(level: &Level) -> GameResult<Option<Progression>> {
+        match &level.waves {
+            Some(path) => {
+                let table = WaveTable::load(resources::resolve(path))?;
+                let layouts = MainState::load_layouts(level)?;
+                let layout_names: Vec<&str> = layouts.iter().map(|layout| layout.name.as_str()).collect();
+                table
+                    .check_level_archetypes(&level.archetypes)
+                    .and_then(|()| table.check_layouts(&layout_names))
+                    .map_err(|e| GameError::CustomError(format!("Invalid wave table {}: {}", path, e)))?;
+                Ok(Some(Progression::new(table)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // The layout files, with the level's own clusters first so they win over a layout
+    // file that happens to share the level's name
+    fn load_layouts(level: &Level) -> GameResult<Vec<ClusterLayout>> {
+        let mut layouts = ClusterLayout::load_dir(resources::resolve(cluster_layout::LAYOUTS_DIR))?;
+        let inline_layout = level.inline_layout();
+        if !inline_layout.clusters.is_empty() {
+            layouts.insert(0, inline_layout);
+        }
+        Ok(layouts)
+    }
+
+    // The level's field, with the current wave's settings laid over it
+    fn build_collectibles(
+        level: &Level,
+        progression: Option<&Progression>,
+        seed: WorldSeed,
+    ) -> GameResult<Collectibles> {
+        let settings = &level.collectibles;
+        let generator = settings.generate.as_ref();
+        let wave = progression.map(|progression| progression.current());
+        let base_archetype = Archetype {
+            hit_points: settings.hit_points,
+            ..Archetype::default()
         };
+        // Every wave gets a world of its own, all derived from the run seed
+        let seed = match progression {
+            Some(progression) => seed.child(&format!("wave{}", progression.wave_number())),
+            None => seed,
+        };
+        let source = match generator {
+            Some(generator) => match &generator.density {
+                Some(density) => SpawnSource::Density(density.clone()),
+                None => SpawnSource::Layout,
+            },
+            None => SpawnSource::Fixed,
+        };
+        let layouts = MainState::load_layouts(level)?;
+        let has_inline_layout = !level.inline_layout().clusters.is_empty();
+        // Clusters drawn into the level are its shape, so waves only get to pick a layout
+        // for levels that don't have their own
+        let layout_name = has_inline_layout
+            .then_some(level.name.as_str())
+            .or_else(|| wave.and_then(|wave| wave.layout.as_deref()))
+            .or_else(|| generator.and_then(|g| g.layout.as_deref()));
+        let field = FieldSettings {
+            screen_width: level.world.width,
+            screen_height: level.world.height,
+            collectible_count: match (generator, wave) {
+                (Some(_), Some(wave)) => wave.count,
+                (Some(generator), None) => generator.count,
+                // Validation keeps waves out of placements-only levels
+                (None, _) => 0,
+            },
+            cluster_size: generator.map_or(0.0, |g| g.cluster_size),
+            proximity_threshold: wave
+                .and_then(|wave| wave.proximity_threshold)
+                .unwrap_or(settings.proximity_threshold),
+            collectible_size: settings.size,
+            seed,
+        };
+        let mut collectibles = Collectibles::new(
+            field,
+            progression.map_or_else(|| vec![base_archetype.clone()], |p| p.archetypes(&base_archetype)),
+            layouts,
+            layout_name,
+            source,
+            MainState::fixed_placements(level, &base_archetype),
+            MainState::placement_constraints(level),
+        )?;
+        if let Some(wave) = wave {
+            collectibles.aggression = wave.aggression;
+        }
+        Ok(collectibles)
+    }
 
-        let button_width = 150.0;
-        let button_height = 50.0;
+    fn fixed_placements(level: &Level, base_archetype: &Archetype) -> Vec<FixedPlacement> {
+        level
+            .collectibles
+            .placements
+            .iter()
+            .map(|placement| FixedPlacement {
+                position: placement.into(),
+                archetype: placement
+                    .archetype
+                    .as_deref()
+                    .and_then(|name| level.archetype(name))
+                    .map(|params| params.build(base_archetype)),
+            })
+            .collect()
+    }
 
-        // Position the button at the bottom-center of the screen
-        let button_x = 1000.0; // center horizontally
-        let button_y = 200.0; // 20 pixels from the bottom
+    // Keeps generated collectibles off the spawn, the obstacles, the placed collectibles and the UI
+    fn placement_constraints(level: &Level) -> PlacementConstraints {
+        let mut constraints = PlacementConstraints::new();
+        constraints.keep_away_from_spawn(level.player_spawn.into(), level.collectibles.min_spawn_distance);
+        for zone in &level.exclusion_zones {
+            constraints.exclude(*zone);
+        }
+        for obstacle in &level.obstacles {
+            constraints.exclude_rect(obstacle.x, obstacle.y, obstacle.width, obstacle.height);
+        }
+        // Leave room around the collectibles the level places itself
+        for placement in &level.collectibles.placements {
+            constraints.exclude_circle(placement.into(), level.collectibles.size);
+        }
+        let button = level.ui.restart_button;
+        constraints.exclude_rect(button.x, button.y, button.width, button.height);
+        constraints
+    }
 
-        let restart_button = Button::new(
+    fn build_restart_button(ctx: &mut Context, anchor: Anchor) -> GameResult<Button> {
+        Button::new(
             ctx,
             Point2 {
-                x: button_x,
-                y: button_y,
+                x: anchor.x,
+                y: anchor.y,
             },
             Vector2 {
-                x: button_width,
-                y: button_height,
+                x: anchor.width,
+                y: anchor.height,
             },
             "Restart",
-        )?;
+        )
+    }
 
-        //Initialize multiple smoke effects and put them into a pool
-        let mut smoke_effect_pool = Vec::new();
-        for _ in 0..PARTICLES_IN_SMOKE {
-            smoke_effect_pool.push(SmokeEffect::new_inactive());
+    fn build_background(ctx: &mut Context, level: &Level) -> GameResult<Option<TileBackground>> {
+        level
+            .background
+            .as_ref()
+            .map(|path| TileBackground::load(ctx, path))
+            .transpose()
+    }
+
+    fn build_seed_label(level: &Level, progression: Option<&Progression>, seed: WorldSeed) -> graphics::Text {
+        match progression {
+            Some(progression) => graphics::Text::new(format!(
+                "level: {}  wave: {}  seed: {}",
+                level.name,
+                progression.wave_number(),
+                seed
+            )),
+            None => graphics::Text::new(format!("level: {}  seed: {}", level.name, seed)),
         }
+    }
 
-        let player = Player::new(Point2 {
-            x: PLAYER_POSITION_X,
-            y: PLAYER_POSITION_Y,
-        });
+    // Reads the level file again and rebuilds the field, spawn and UI from it
+    fn reload_level(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let level = Level::load(&self.level_path)?;
+        self.apply_level(ctx, level)
+    }
 
-        Ok(MainState {
-            event_handler,
-            player,
-            collectibles,
-            smoke_effect_pool,
-            restart_button,
-            default_player_position,
-        })
+    // Starts `level` over from its first wave
+    fn apply_level(&mut self, ctx: &mut Context, level: Level) -> GameResult<()> {
+        let progression = MainState::load_progression(&level)?;
+        self.collectibles = MainState::build_collectibles(&level, progression.as_ref(), self.seed)?;
+        MainState::log_placement(&self.collectibles.placement_report);
+        self.restart_button = MainState::build_restart_button(ctx, level.ui.restart_button)?;
+        self.background = MainState::build_background(ctx, &level)?;
+        self.seed_label = MainState::build_seed_label(&level, progression.as_ref(), self.seed);
+        self.progression = progression;
+        self.player.default_position = level.player_spawn.into();
+        self.level = level;
+        self.reset_player_position();
+        Ok(())
+    }
+
+    // Moves on to the next wave once the field has been cleared
+    fn advance_wave(&mut self) -> GameResult<()> {
+        let progression = match self.progression.as_mut() {
+            Some(progression) => progression,
+            None => return Ok(()),
+        };
+        progression.advance();
+        self.collectibles = MainState::build_collectibles(&self.level, self.progression.as_ref(), self.seed)?;
+        MainState::log_placement(&self.collectibles.placement_report);
+        self.seed_label = MainState::build_seed_label(&self.level, self.progression.as_ref(), self.seed);
+        Ok(())
+    }
+
+    // A crowded level still plays, but whoever made it should hear about it
+    fn log_placement(report: &PlacementReport) {
+        if report.was_relaxed() {
+            println!("Placement relaxed: {}", report.summary());
+        }
+    }
+
+    fn toggle_editor(&mut self, ctx: &mut Context) {
+        if !self.editor.active {
+            // The level's own layout, which isn't necessarily the one the current wave uses
+            let layout = self
+                .level
+                .collectibles
+                .generate
+                .as_ref()
+                .and_then(|generator| generator.layout.as_deref())
+                .and_then(|name| self.collectibles.layout_named(name));
+            self.editor.open(&self.level, layout);
+            println!("editing {}", self.level.name);
+            return;
+        }
+        // Play the edited level, or stay in the editor until it's fixed
+        let level = self.editor.level().clone();
+        if let Err(e) = level.validate() {
+            println!("Can't play the edited level: {}", e);
+            return;
+        }
+        match self.apply_level(ctx, level) {
+            Ok(()) => self.editor.close(),
+            Err(e) => println!("Can't play the edited level: {}", e),
+        }
     }
 
+    fn save_edited_level(&self) {
+        // Tiled maps stay Tiled's to edit; the edited level goes into a level file next to the map
+        let path = Path::new(&self.level_path);
+        let path = if path.extension().is_some_and(|ext| ext == tiled_map::MAP_EXTENSION) {
+            path.with_extension("json")
+        } else {
+            path.to_path_buf()
+        };
+        match self.editor.level().save(&path) {
+            Ok(()) => println!("saved {}", path.display()),
+            Err(e) => println!("{}", e),
+        }
+    }
 
+    fn keep_player_out_of_obstacles(&mut self) {
+        for obstacle in &self.level.obstacles {
+            self.player.position = obstacle.push_out(self.player.position, self.player.radius);
+        }
+    }
 }
 
 impl event::EventHandler<ggez::GameError> for MainState {
@@ -100,51 +338,120 @@ impl event::EventHandler<ggez::GameError> for MainState {
         x: f32,
         y: f32,
     ) {
+        if self.editor.active {
+            if let Some(EditorCommand::Save) = self.editor.mouse_button_down(button, mint::Point2 { x, y }) {
+                self.save_edited_level();
+            }
+            return;
+        }
         // Check if the left mouse button was clicked
         if button == ggez::input::mouse::MouseButton::Left {
             if self.restart_button.is_clicked(mint::Point2 { x, y }) {
-                self.collectibles.reset_collectibles(ctx);
-                self.reset_player_position();
+                if let Err(e) = self.reload_level(ctx) {
+                    // Keep playing the level as it was last loaded
+                    println!("Failed to reload {}: {}", self.level_path, e);
+                    match self.collectibles.reset_collectibles() {
+                        Ok(report) => MainState::log_placement(&report),
+                        Err(e) => println!("Failed to reset collectibles: {}", e),
+                    }
+                    self.reset_player_position();
+                }
                 println!("button clicked");
             }
         }
     }
+    fn mouse_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        _button: ggez::input::mouse::MouseButton,
+        _x: f32,
+        _y: f32,
+    ) {
+        if self.editor.active {
+            self.editor.mouse_button_up();
+        }
+    }
+    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if self.editor.active {
+            self.editor.mouse_motion(mint::Point2 { x, y });
+        }
+    }
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods, repeat: bool) {
+        if keycode == editor::EDITOR_KEY && !repeat {
+            self.toggle_editor(ctx);
+            return;
+        }
+        if self.editor.active {
+            if let Some(tool) = Tool::for_key(keycode) {
+                self.editor.select_tool(tool);
+            }
+            return;
+        }
+        // Cycle through the cluster layouts
+        if keycode == KeyCode::L {
+            match self.collectibles.next_layout() {
+                Ok(report) => MainState::log_placement(&report),
+                Err(e) => println!("Failed to switch layout: {}", e),
+            }
+            self.reset_player_position();
+            println!("cluster layout: {}", self.collectibles.layout().name);
+        }
+    }
     fn update(&mut self, ctx: &mut Context) -> GameResult {
         let dt = ggez::timer::delta(ctx).as_secs_f32();
+        if self.editor.active {
+            return Ok(());
+        }
 
         // Handle gamepad input
         self.event_handler.process_events(&mut self.player);
 
         // Update Collectibles
-        self.collectibles.update(ctx, dt, self.player.position, &self.player);
+        self.collectibles.update(ctx, dt, self.player.position, &mut self.player, &mut self.effects)?;
+        // A wave where nothing could be placed would otherwise skip ahead every frame
+        let wave_played = self.collectibles.placement_report.placed > 0;
+        if self.progression.is_some() && wave_played && self.collectibles.is_cleared() {
+            self.advance_wave()?;
+        }
 
         // Update all smoke effects
-        for effect in &mut self.smoke_effect_pool {
-            effect.update(dt);
-        }
+        self.effects.update(dt);
         // Update the player
         self.player.update(dt);
+        self.keep_player_out_of_obstacles();
 
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         graphics::clear(ctx, graphics::Color::from_rgb(0, 0, 0));
+        if let Some(background) = &self.background {
+            background.draw(ctx)?;
+        }
 
    
       
 
-        // Draw each active smoke effect
-        for effect in &self.smoke_effect_pool {
-            if effect.is_active() {
-                effect.draw(ctx)?;
-            }
+        for obstacle in &self.level.obstacles {
+            obstacle.draw(ctx)?;
+        }
+        // The editor shows the level being edited instead of the paused game
+        if self.editor.active {
+            self.editor.draw(ctx)?;
+            return graphics::present(ctx);
         }
+        // Draw each active smoke effect
+        self.effects.draw(ctx)?;
         // Draw the player
         self.player.draw(ctx)?;
-        self.collectibles.draw(ctx, self.player.position);
+        self.collectibles.draw(ctx, self.player.position)?;
         //Draw UI
         self.restart_button.draw(ctx)?;
+        graphics::draw(
+            ctx,
+            &self.seed_label,
+            (Point2::from(self.level.ui.seed_label),),
+        )?;
         graphics::present(ctx)
     }
 }
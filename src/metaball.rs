@@ -0,0 +1,235 @@
+// Implicit-surface rendering for blobs that sit close to each other.
+// Everything in here is plain CPU math on points, so it needs no graphics context.
+use mint::Point2;
+
+// The field is normalized so that a lone blob's surface sits exactly at its radius
+pub const SURFACE_THRESHOLD: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetaBlob {
+    pub center: Point2<f32>,
+    pub radius: f32,
+}
+
+// Triangles filling the merged shape plus the line segments of its outline
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Contour {
+    // Flat list of triangle corners, three per triangle
+    pub fill: Vec<Point2<f32>>,
+    pub outline: Vec<(Point2<f32>, Point2<f32>)>,
+}
+
+// Sum of every blob's influence at `point`. Each blob's influence fades to zero at
+// `radius + merge_distance`, so blobs further apart than that never fuse.
+pub fn field_value(blobs: &[MetaBlob], merge_distance: f32, point: Point2<f32>) -> f32 {
+    blobs
+        .iter()
+        .map(|blob| {
+            let reach = blob.radius + merge_distance.max(0.001);
+            let dx = point.x - blob.center.x;
+            let dy = point.y - blob.center.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            falloff(distance / reach) / falloff(blob.radius / reach)
+        })
+        .sum()
+}
+
+fn falloff(t: f32) -> f32 {
+    if t >= 1.0 {
+        0.0
+    } else {
+        let inv = 1.0 - t * t;
+        inv * inv
+    }
+}
+
+// Groups blobs whose surfaces are within `merge_distance` of each other, transitively.
+// Returns indices into `blobs`; every blob ends up in exactly one group.
+pub fn group_blobs(blobs: &[MetaBlob], merge_distance: f32) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..blobs.len()).collect();
+
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for i in 0..blobs.len() {
+        for j in (i + 1)..blobs.len() {
+            let dx = blobs[i].center.x - blobs[j].center.x;
+            let dy = blobs[i].center.y - blobs[j].center.y;
+            let gap = (dx * dx + dy * dy).sqrt() - blobs[i].radius - blobs[j].radius;
+            if gap < merge_distance {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root = vec![usize::MAX; blobs.len()];
+    for i in 0..blobs.len() {
+        let r = root(&mut parent, i);
+        if group_of_root[r] == usize::MAX {
+            group_of_root[r] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[group_of_root[r]].push(i);
+    }
+    groups
+}
+
+// Marching squares over the blobs' field. `cell_size` trades smoothness for speed.
+pub fn marching_squares(blobs: &[MetaBlob], merge_distance: f32, cell_size: f32) -> Contour {
+    let mut contour = Contour::default();
+    if blobs.is_empty() || cell_size <= 0.0 {
+        return contour;
+    }
+
+    // Only the area the field can reach needs sampling
+    let margin = merge_distance.max(0.0) + cell_size;
+    let min_x = blobs.iter().map(|b| b.center.x - b.radius).fold(f32::INFINITY, f32::min) - margin;
+    let min_y = blobs.iter().map(|b| b.center.y - b.radius).fold(f32::INFINITY, f32::min) - margin;
+    let max_x = blobs.iter().map(|b| b.center.x + b.radius).fold(f32::NEG_INFINITY, f32::max) + margin;
+    let max_y = blobs.iter().map(|b| b.center.y + b.radius).fold(f32::NEG_INFINITY, f32::max) + margin;
+    let columns = ((max_x - min_x) / cell_size).ceil() as usize + 1;
+    let rows = ((max_y - min_y) / cell_size).ceil() as usize + 1;
+
+    let corner = |column: usize, row: usize| Point2 {
+        x: min_x + column as f32 * cell_size,
+        y: min_y + row as f32 * cell_size,
+    };
+    let mut samples = vec![0.0; columns * rows];
+    for row in 0..rows {
+        for column in 0..columns {
+            samples[row * columns + column] = field_value(blobs, merge_distance, corner(column, row));
+        }
+    }
+
+    for row in 0..rows - 1 {
+        for column in 0..columns - 1 {
+            // Corners in clockwise order starting top-left
+            let cell = [(column, row), (column + 1, row), (column + 1, row + 1), (column, row + 1)];
+            let points: Vec<Point2<f32>> = cell.iter().map(|&(c, r)| corner(c, r)).collect();
+            let values: Vec<f32> = cell.iter().map(|&(c, r)| samples[r * columns + c]).collect();
+            march_cell(&points, &values, &mut contour);
+        }
+    }
+    contour
+}
+
+// Walks the cell's perimeter, keeping inside corners and the points where an edge crosses
+// the surface. The kept points form a convex polygon that is fanned into triangles.
+fn march_cell(points: &[Point2<f32>], values: &[f32], contour: &mut Contour) {
+    let inside = |i: usize| values[i] >= SURFACE_THRESHOLD;
+    let mut polygon = Vec::with_capacity(8);
+    let mut crossings = Vec::with_capacity(4);
+
+    for i in 0..4 {
+        let j = (i + 1) % 4;
+        if inside(i) {
+            polygon.push(points[i]);
+        }
+        if inside(i) != inside(j) {
+            let t = (SURFACE_THRESHOLD - values[i]) / (values[j] - values[i]);
+            let crossing = Point2 {
+                x: points[i].x + (points[j].x - points[i].x) * t,
+                y: points[i].y + (points[j].y - points[i].y) * t,
+            };
+            polygon.push(crossing);
+            crossings.push(crossing);
+        }
+    }
+
+    for i in 1..polygon.len().saturating_sub(1) {
+        contour.fill.push(polygon[0]);
+        contour.fill.push(polygon[i]);
+        contour.fill.push(polygon[i + 1]);
+    }
+    for pair in crossings.chunks(2) {
+        if let [a, b] = pair {
+            contour.outline.push((*a, *b));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob(x: f32, y: f32, radius: f32) -> MetaBlob {
+        MetaBlob {
+            center: Point2 { x, y },
+            radius,
+        }
+    }
+
+    // Joins outline segments that share an end point and returns the number of loops,
+    // failing if any loop is left open
+    fn closed_loops(contour: &Contour) -> usize {
+        let mut points: Vec<Point2<f32>> = Vec::new();
+        let mut index = |p: Point2<f32>| {
+            let close = |q: &Point2<f32>| (q.x - p.x).abs() < 0.01 && (q.y - p.y).abs() < 0.01;
+            points.iter().position(close).unwrap_or_else(|| {
+                points.push(p);
+                points.len() - 1
+            })
+        };
+        let edges: Vec<(usize, usize)> = contour.outline.iter().map(|&(a, b)| (index(a), index(b))).collect();
+
+        let mut degree = vec![0; points.len()];
+        let mut parent: Vec<usize> = (0..points.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                i = parent[i];
+            }
+            i
+        }
+        for &(a, b) in &edges {
+            degree[a] += 1;
+            degree[b] += 1;
+            let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+            parent[ra] = rb;
+        }
+        assert!(degree.iter().all(|&d| d == 2), "outline has open ends");
+        (0..points.len()).filter(|&i| root(&mut parent, i) == i).count()
+    }
+
+    #[test]
+    fn field_is_inside_a_lone_blob_and_outside_far_away() {
+        let blobs = [blob(0.0, 0.0, 20.0)];
+        assert!(field_value(&blobs, 10.0, Point2 { x: 0.0, y: 0.0 }) >= SURFACE_THRESHOLD);
+        assert!(field_value(&blobs, 10.0, Point2 { x: 19.0, y: 0.0 }) >= SURFACE_THRESHOLD);
+        assert!(field_value(&blobs, 10.0, Point2 { x: 25.0, y: 0.0 }) < SURFACE_THRESHOLD);
+        assert_eq!(field_value(&blobs, 10.0, Point2 { x: 100.0, y: 0.0 }), 0.0);
+    }
+
+    #[test]
+    fn blobs_group_only_within_merge_distance() {
+        // Surfaces 5 apart merge at a merge distance of 10; the third blob is 50 further on
+        let blobs = [blob(0.0, 0.0, 10.0), blob(25.0, 0.0, 10.0), blob(95.0, 0.0, 10.0)];
+        assert_eq!(group_blobs(&blobs, 10.0), vec![vec![0, 1], vec![2]]);
+        // Merging is transitive once the gap to the third blob is covered too
+        assert_eq!(group_blobs(&blobs, 60.0), vec![vec![0, 1, 2]]);
+        assert_eq!(group_blobs(&blobs, 1.0).len(), 3);
+    }
+
+    #[test]
+    fn overlapping_blobs_give_one_contour() {
+        // Off-grid sizes so no sample lands exactly on the surface
+        let blobs = [blob(0.3, 0.7, 19.6), blob(30.3, 0.7, 19.6)];
+        let contour = marching_squares(&blobs, 10.0, 2.0);
+        assert!(!contour.fill.is_empty());
+        assert_eq!(contour.fill.len() % 3, 0);
+        assert_eq!(closed_loops(&contour), 1);
+    }
+
+    #[test]
+    fn separated_blobs_give_two_contours() {
+        let blobs = [blob(0.3, 0.7, 19.6), blob(200.3, 0.7, 19.6)];
+        let contour = marching_squares(&blobs, 10.0, 2.0);
+        assert_eq!(closed_loops(&contour), 2);
+    }
+}
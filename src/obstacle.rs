@@ -0,0 +1,70 @@
+use ggez::graphics::{self, Color, Rect};
+use ggez::{Context, GameResult};
+use mint::Point2;
+use serde::{Deserialize, Serialize};
+
+pub const OBSTACLE_COLOR: Color = Color::new(0.3, 0.3, 0.35, 1.0);
+
+// A solid axis-aligned rectangle the player can't move through
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Obstacle {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Obstacle {
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+
+    pub fn contains(&self, point: Point2<f32>) -> bool {
+        point.x >= self.x
+            && point.x <= self.x + self.width
+            && point.y >= self.y
+            && point.y <= self.y + self.height
+    }
+
+    // Where a circle at `center` has to move to so it no longer overlaps the obstacle.
+    // Returns `center` unchanged if they don't touch.
+    pub fn push_out(&self, center: Point2<f32>, radius: f32) -> Point2<f32> {
+        let closest = Point2 {
+            x: center.x.clamp(self.x, self.x + self.width),
+            y: center.y.clamp(self.y, self.y + self.height),
+        };
+        let dx = center.x - closest.x;
+        let dy = center.y - closest.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+
+        if distance > 0.0 {
+            if distance >= radius {
+                return center;
+            }
+            let push = radius - distance;
+            return Point2 {
+                x: center.x + dx / distance * push,
+                y: center.y + dy / distance * push,
+            };
+        }
+
+        // Center is inside the rectangle: leave through the nearest edge
+        let exits = [
+            (center.x - self.x, Point2 { x: self.x - radius, y: center.y }),
+            (self.x + self.width - center.x, Point2 { x: self.x + self.width + radius, y: center.y }),
+            (center.y - self.y, Point2 { x: center.x, y: self.y - radius }),
+            (self.y + self.height - center.y, Point2 { x: center.x, y: self.y + self.height + radius }),
+        ];
+        exits
+            .iter()
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|exit| exit.1)
+            .unwrap_or(center)
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        let mesh = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), self.rect(), OBSTACLE_COLOR)?;
+        graphics::draw(ctx, &mesh, graphics::DrawParam::default())
+    }
+}
@@ -0,0 +1,115 @@
+use crate::world_seed::WorldSeed;
+use rand::Rng;
+use std::ops::Range;
+
+// Ranges a collectible's personality is drawn from
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersonalityRanges {
+    // Multiplier on how far the outline wobbles away from a circle
+    pub wobble_amplitude: Range<f32>,
+    // Multiplier on how fast the outline wobbles
+    pub wobble_speed: Range<f32>,
+    // How far (in pixels) the tentacle snakes sideways
+    pub tentacle_noise_scale: Range<f32>,
+    // Number of points on the outline
+    pub point_count: Range<usize>,
+}
+
+impl Default for PersonalityRanges {
+    fn default() -> Self {
+        PersonalityRanges {
+            wobble_amplitude: 0.7..1.3,
+            wobble_speed: 0.7..1.4,
+            tentacle_noise_scale: 15.0..31.0,
+            point_count: 16..26,
+        }
+    }
+}
+
+// What makes one collectible look and move differently from the next
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Personality {
+    // The collectible's own seed; its eyes (and anything else random about it) draw from
+    // named streams of it, so they replay with the run
+    pub seed: WorldSeed,
+    pub noise_seed: u32,
+    pub wobble_amplitude: f32,
+    pub wobble_speed: f32,
+    pub tentacle_noise_scale: f32,
+    pub point_count: usize,
+}
+
+impl Personality {
+    pub fn random<R: Rng + ?Sized>(rng: &mut R, ranges: &PersonalityRanges) -> Self {
+        Personality {
+            seed: WorldSeed(rng.gen()),
+            noise_seed: rng.gen(),
+            wobble_amplitude: pick(rng, &ranges.wobble_amplitude),
+            wobble_speed: pick(rng, &ranges.wobble_speed),
+            tentacle_noise_scale: pick(rng, &ranges.tentacle_noise_scale),
+            point_count: if ranges.point_count.is_empty() {
+                ranges.point_count.start
+            } else {
+                rng.gen_range(ranges.point_count.clone())
+            }
+            .max(3),
+        }
+    }
+}
+
+// `gen_range` panics on an empty range, so a collapsed range just yields its start
+fn pick<R: Rng + ?Sized>(rng: &mut R, range: &Range<f32>) -> f32 {
+    if range.start < range.end {
+        rng.gen_range(range.clone())
+    } else {
+        range.start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn personalities_stay_inside_their_ranges() {
+        let ranges = PersonalityRanges {
+            wobble_amplitude: 0.5..0.6,
+            wobble_speed: 2.0..3.0,
+            tentacle_noise_scale: 10.0..11.0,
+            point_count: 7..9,
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        for _ in 0..200 {
+            let personality = Personality::random(&mut rng, &ranges);
+            assert!(ranges.wobble_amplitude.contains(&personality.wobble_amplitude));
+            assert!(ranges.wobble_speed.contains(&personality.wobble_speed));
+            assert!(ranges.tentacle_noise_scale.contains(&personality.tentacle_noise_scale));
+            assert!(ranges.point_count.contains(&personality.point_count));
+        }
+    }
+
+    #[test]
+    fn collapsed_ranges_give_their_value() {
+        let ranges = PersonalityRanges {
+            wobble_amplitude: 1.0..1.0,
+            wobble_speed: 1.0..1.0,
+            tentacle_noise_scale: 23.0..23.0,
+            point_count: 2..2,
+        };
+        let personality = Personality::random(&mut ChaCha8Rng::seed_from_u64(5), &ranges);
+        assert_eq!(personality.wobble_amplitude, 1.0);
+        assert_eq!(personality.tentacle_noise_scale, 23.0);
+        // Too few points for an outline are raised to a triangle
+        assert_eq!(personality.point_count, 3);
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_personality() {
+        let ranges = PersonalityRanges::default();
+        let draw = |seed: u64| Personality::random(&mut ChaCha8Rng::seed_from_u64(seed), &ranges);
+        assert_eq!(draw(9), draw(9));
+        assert_ne!(draw(9), draw(10));
+    }
+}
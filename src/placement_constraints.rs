@@ -0,0 +1,155 @@
+// Rules for where collectibles may not be placed: exclusion zones (e.g. under UI or
+// obstacles), a minimum distance from the player spawn, and arbitrary caller predicates.
+use mint::Point2;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+pub const DEFAULT_MIN_SPAWN_DISTANCE: f32 = 300.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ExclusionZone {
+    Circle { x: f32, y: f32, radius: f32 },
+    Rect { x: f32, y: f32, width: f32, height: f32 },
+}
+
+impl ExclusionZone {
+    // True if a circle of radius `clearance` at `position` overlaps the zone
+    pub fn blocks(&self, position: Point2<f32>, clearance: f32) -> bool {
+        match *self {
+            ExclusionZone::Circle { x, y, radius } => {
+                let dx = position.x - x;
+                let dy = position.y - y;
+                let reach = radius + clearance;
+                dx * dx + dy * dy < reach * reach
+            }
+            ExclusionZone::Rect { x, y, width, height } => {
+                let closest_x = position.x.clamp(x, x + width);
+                let closest_y = position.y.clamp(y, y + height);
+                let dx = position.x - closest_x;
+                let dy = position.y - closest_y;
+                // Inside the rectangle, or closer to it than the clearance
+                (dx == 0.0 && dy == 0.0) || dx * dx + dy * dy < clearance * clearance
+            }
+        }
+    }
+}
+
+// Returns true if a collectible may go at the given position
+pub type PlacementPredicate = Rc<dyn Fn(Point2<f32>) -> bool>;
+
+#[derive(Clone, Default)]
+pub struct PlacementConstraints {
+    pub zones: Vec<ExclusionZone>,
+    pub spawn: Option<Point2<f32>>,
+    pub min_spawn_distance: f32,
+    predicates: Vec<PlacementPredicate>,
+}
+
+impl PlacementConstraints {
+    pub fn new() -> Self {
+        PlacementConstraints::default()
+    }
+
+    pub fn exclude(&mut self, zone: ExclusionZone) -> &mut Self {
+        self.zones.push(zone);
+        self
+    }
+
+    pub fn exclude_circle(&mut self, center: Point2<f32>, radius: f32) -> &mut Self {
+        self.exclude(ExclusionZone::Circle {
+            x: center.x,
+            y: center.y,
+            radius,
+        })
+    }
+
+    pub fn exclude_rect(&mut self, x: f32, y: f32, width: f32, height: f32) -> &mut Self {
+        self.exclude(ExclusionZone::Rect { x, y, width, height })
+    }
+
+    // Keeps every collectible's center at least `distance` away from `spawn`
+    pub fn keep_away_from_spawn(&mut self, spawn: Point2<f32>, distance: f32) -> &mut Self {
+        self.spawn = Some(spawn);
+        self.min_spawn_distance = distance;
+        self
+    }
+
+    // The game's own rules are all zones so far; this is for callers with rules of their own
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn add_predicate<F: Fn(Point2<f32>) -> bool + 'static>(&mut self, predicate: F) -> &mut Self {
+        self.predicates.push(Rc::new(predicate));
+        self
+    }
+
+    // `clearance` is the collectible's radius, so zones keep the whole body out
+    pub fn allows(&self, position: Point2<f32>, clearance: f32) -> bool {
+        if let Some(spawn) = self.spawn {
+            let dx = position.x - spawn.x;
+            let dy = position.y - spawn.y;
+            if dx * dx + dy * dy < self.min_spawn_distance * self.min_spawn_distance {
+                return false;
+            }
+        }
+        self.zones.iter().all(|zone| !zone.blocks(position, clearance))
+            && self.predicates.iter().all(|predicate| predicate(position))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32, y: f32) -> Point2<f32> {
+        Point2 { x, y }
+    }
+
+    #[test]
+    fn rect_zones_keep_the_whole_body_out() {
+        let mut constraints = PlacementConstraints::new();
+        constraints.exclude_rect(100.0, 100.0, 200.0, 50.0);
+        assert!(!constraints.allows(at(150.0, 120.0), 0.0));
+        // Outside, but the body would reach into the rectangle
+        assert!(!constraints.allows(at(150.0, 170.0), 30.0));
+        assert!(constraints.allows(at(150.0, 170.0), 10.0));
+        // Diagonal from a corner the distance is to the corner itself
+        assert!(constraints.allows(at(320.0, 170.0), 25.0));
+        assert!(!constraints.allows(at(320.0, 170.0), 30.0));
+    }
+
+    #[test]
+    fn circle_zones_add_the_clearance_to_the_radius() {
+        let mut constraints = PlacementConstraints::new();
+        constraints.exclude_circle(at(0.0, 0.0), 50.0);
+        assert!(!constraints.allows(at(40.0, 0.0), 0.0));
+        assert!(constraints.allows(at(60.0, 0.0), 5.0));
+        assert!(!constraints.allows(at(60.0, 0.0), 15.0));
+    }
+
+    #[test]
+    fn spawn_distance_is_measured_between_centers() {
+        let mut constraints = PlacementConstraints::new();
+        constraints.keep_away_from_spawn(at(500.0, 500.0), 300.0);
+        assert!(!constraints.allows(at(700.0, 500.0), 0.0));
+        assert!(!constraints.allows(at(710.0, 710.0), 0.0));
+        assert!(constraints.allows(at(500.0, 800.0), 40.0));
+        assert!(constraints.allows(at(100.0, 100.0), 0.0));
+    }
+
+    #[test]
+    fn predicates_can_reject_positions() {
+        let mut constraints = PlacementConstraints::new();
+        assert!(constraints.allows(at(10.0, 900.0), 0.0));
+        constraints.add_predicate(|position| position.y < 800.0);
+        assert!(!constraints.allows(at(10.0, 900.0), 0.0));
+        assert!(constraints.allows(at(10.0, 700.0), 0.0));
+    }
+
+    #[test]
+    fn zones_read_from_level_files() {
+        let zone: ExclusionZone =
+            serde_json::from_str(r#"{ "shape": "rect", "x": 0, "y": 0, "width": 400, "height": 80 }"#).unwrap();
+        assert_eq!(zone, ExclusionZone::Rect { x: 0.0, y: 0.0, width: 400.0, height: 80.0 });
+        assert!(serde_json::from_str::<ExclusionZone>(r#"{ "shape": "star", "x": 0, "y": 0 }"#).is_err());
+    }
+}
@@ -8,9 +8,9 @@ pub const CIRCLE_SMOOTHNESS: f32 = 0.1;
 pub const MAX_CIRCLE_RADIUS: f32 = 50.0;
 pub const MIN_CIRCLE_RADIUS: f32 = 46.0;
 pub const CIRCLE_RADIUS: f32 = 35.0;
-pub const PLAYER_START_X_POS: f32 = 200.0;
-pub const PLAYER_START_Y_POS: f32 = 200.0;
 pub const PLAYER_ACCELERATION: f32 = 800.0;
+pub const MAGNET_RADIUS: f32 = 250.0;
+pub const MAX_MAGNET_RADIUS: f32 = 600.0;
 
 pub struct Player {
     pub position: mint::Point2<f32>,
@@ -23,6 +23,7 @@ pub struct Player {
     pub max_speed: f32,
     pub radius: f32,
     pub direction: mint::Vector2<f32>,
+    pub magnet_radius: f32,
 }
 
 impl Player {
@@ -38,6 +39,7 @@ impl Player {
             max_speed: MOVEMENT_SPEED,
             radius: CIRCLE_RADIUS,
             direction: mint::Vector2 { x: 0.0, y: 0.0 },
+            magnet_radius: MAGNET_RADIUS,
         }
     }
 
@@ -46,6 +48,11 @@ impl Player {
         self.update_rotation(dt);
     }
 
+    // Grows the magnet radius, e.g. when a power-up is picked up
+    pub fn upgrade_magnet(&mut self, amount: f32) {
+        self.magnet_radius = (self.magnet_radius + amount).min(MAX_MAGNET_RADIUS);
+    }
+
     fn update_movement(&mut self, dt: f32) {
         // Movement logic
         if self.axis_left.0 != 0.0 || self.axis_left.1 != 0.0 {
@@ -0,0 +1,233 @@
+// Blue-noise sampling (Bridson's Poisson-disk algorithm) restricted to squares around
+// cluster centers. No two samples end up closer than the minimum distance.
+use mint::Point2;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+pub const DEFAULT_ATTEMPTS: usize = 30; // candidates tried around a sample before it's retired
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub position: Point2<f32>,
+    // Index into the regions the sample was grown from
+    pub cluster: usize,
+}
+
+// A square of half-size `spread` around `center` that should receive `quota` samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub center: Point2<f32>,
+    pub spread: f32,
+    pub quota: usize,
+}
+
+// Scatters samples over the regions. Each region with a quota first gets one sample near
+// its center (in random order), then regions grow outwards while staying inside their
+// square and inside `min`..`max`, until every quota is met or nothing more fits.
+// Candidates that `accept` rejects are skipped as if they were too close to a neighbour.
+pub fn sample_clusters<R: Rng + ?Sized>(
+    rng: &mut R,
+    regions: &[Region],
+    min: Point2<f32>,
+    max: Point2<f32>,
+    min_distance: f32,
+    attempts: usize,
+    accept: &dyn Fn(Point2<f32>) -> bool,
+) -> Vec<Sample> {
+    let mut samples: Vec<Sample> = Vec::new();
+    if regions.is_empty() || max.x <= min.x || max.y <= min.y {
+        return samples;
+    }
+    let min_distance = min_distance.max(0.001);
+    let mut grid = Grid::new(min, max, min_distance);
+    let mut placed = vec![0; regions.len()];
+    let mut active: Vec<usize> = Vec::new();
+
+    let in_region = |point: Point2<f32>, cluster: usize| {
+        let region = regions[cluster];
+        let spread = region.spread.max(0.0);
+        point.x >= min.x
+            && point.x <= max.x
+            && point.y >= min.y
+            && point.y <= max.y
+            && (point.x - region.center.x).abs() <= spread
+            && (point.y - region.center.y).abs() <= spread
+            && accept(point)
+    };
+
+    let mut order: Vec<usize> = (0..regions.len()).filter(|&i| regions[i].quota > 0).collect();
+    order.shuffle(rng);
+    for cluster in order {
+        let region = regions[cluster];
+        let spread = region.spread.max(0.0);
+        for _ in 0..attempts.max(1) {
+            let candidate = Point2 {
+                x: region.center.x + rng.gen_range(-1.0..=1.0) * spread,
+                y: region.center.y + rng.gen_range(-1.0..=1.0) * spread,
+            };
+            if in_region(candidate, cluster) && grid.is_free(&samples, candidate) {
+                grid.insert(samples.len(), candidate);
+                active.push(samples.len());
+                samples.push(Sample { position: candidate, cluster });
+                placed[cluster] += 1;
+                break;
+            }
+        }
+    }
+
+    while !active.is_empty() {
+        let slot = rng.gen_range(0..active.len());
+        let parent = samples[active[slot]];
+        if placed[parent.cluster] >= regions[parent.cluster].quota {
+            active.swap_remove(slot);
+            continue;
+        }
+        let mut grew = false;
+        for _ in 0..attempts.max(1) {
+            // Uniform over the annulus between one and two spacings away
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let distance = min_distance * rng.gen_range(1.0f32..4.0).sqrt();
+            let candidate = Point2 {
+                x: parent.position.x + angle.cos() * distance,
+                y: parent.position.y + angle.sin() * distance,
+            };
+            if in_region(candidate, parent.cluster) && grid.is_free(&samples, candidate) {
+                grid.insert(samples.len(), candidate);
+                active.push(samples.len());
+                samples.push(Sample {
+                    position: candidate,
+                    cluster: parent.cluster,
+                });
+                placed[parent.cluster] += 1;
+                grew = true;
+                break;
+            }
+        }
+        if !grew {
+            active.swap_remove(slot);
+        }
+    }
+    samples
+}
+
+// Background grid sized so each cell holds at most one sample
+struct Grid {
+    origin: Point2<f32>,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+    min_distance: f32,
+    cells: Vec<Option<usize>>,
+}
+
+impl Grid {
+    fn new(min: Point2<f32>, max: Point2<f32>, min_distance: f32) -> Self {
+        let cell_size = min_distance / std::f32::consts::SQRT_2;
+        let columns = ((max.x - min.x) / cell_size).ceil() as usize + 1;
+        let rows = ((max.y - min.y) / cell_size).ceil() as usize + 1;
+        Grid {
+            origin: min,
+            cell_size,
+            columns,
+            rows,
+            min_distance,
+            cells: vec![None; columns * rows],
+        }
+    }
+
+    fn cell_of(&self, point: Point2<f32>) -> (usize, usize) {
+        let column = ((point.x - self.origin.x) / self.cell_size).max(0.0) as usize;
+        let row = ((point.y - self.origin.y) / self.cell_size).max(0.0) as usize;
+        (column.min(self.columns - 1), row.min(self.rows - 1))
+    }
+
+    fn insert(&mut self, index: usize, point: Point2<f32>) {
+        let (column, row) = self.cell_of(point);
+        self.cells[row * self.columns + column] = Some(index);
+    }
+
+    // True if no existing sample is within the minimum distance of `point`
+    fn is_free(&self, samples: &[Sample], point: Point2<f32>) -> bool {
+        let (column, row) = self.cell_of(point);
+        for r in row.saturating_sub(2)..=(row + 2).min(self.rows - 1) {
+            for c in column.saturating_sub(2)..=(column + 2).min(self.columns - 1) {
+                if let Some(index) = self.cells[r * self.columns + c] {
+                    let other = samples[index].position;
+                    let dx = other.x - point.x;
+                    let dy = other.y - point.y;
+                    if dx * dx + dy * dy < self.min_distance * self.min_distance {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    const MIN: Point2<f32> = Point2 { x: 0.0, y: 0.0 };
+    const MAX: Point2<f32> = Point2 { x: 1000.0, y: 800.0 };
+
+    fn region(x: f32, y: f32, spread: f32, quota: usize) -> Region {
+        Region {
+            center: Point2 { x, y },
+            spread,
+            quota,
+        }
+    }
+
+    fn sample(seed: u64, regions: &[Region], min_distance: f32, accept: &dyn Fn(Point2<f32>) -> bool) -> Vec<Sample> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        sample_clusters(&mut rng, regions, MIN, MAX, min_distance, DEFAULT_ATTEMPTS, accept)
+    }
+
+    #[test]
+    fn samples_keep_their_distance() {
+        let regions = [region(250.0, 400.0, 200.0, 40), region(700.0, 400.0, 200.0, 40)];
+        for seed in 0..5 {
+            let samples = sample(seed, &regions, 30.0, &|_| true);
+            assert_eq!(samples.len(), 80);
+            for (i, a) in samples.iter().enumerate() {
+                for b in &samples[i + 1..] {
+                    let dx = a.position.x - b.position.x;
+                    let dy = a.position.y - b.position.y;
+                    assert!((dx * dx + dy * dy).sqrt() >= 30.0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_stay_inside_their_region() {
+        // The second region hangs over the bounds, which cut it short
+        let regions = [region(300.0, 300.0, 120.0, 20), region(950.0, 50.0, 150.0, 20)];
+        let samples = sample(3, &regions, 25.0, &|position| position.x < 980.0);
+        for sample in &samples {
+            let region = regions[sample.cluster];
+            assert!((sample.position.x - region.center.x).abs() <= region.spread);
+            assert!((sample.position.y - region.center.y).abs() <= region.spread);
+            assert!(sample.position.x >= MIN.x && sample.position.y >= MIN.y);
+            assert!(sample.position.x <= MAX.x && sample.position.y <= MAX.y);
+            assert!(sample.position.x < 980.0);
+        }
+        for (cluster, region) in regions.iter().enumerate() {
+            assert!(samples.iter().filter(|sample| sample.cluster == cluster).count() <= region.quota);
+        }
+    }
+
+    #[test]
+    fn small_regions_fall_short_of_their_quota() {
+        // A 40px square only fits a handful of samples 30px apart
+        let regions = [region(500.0, 400.0, 20.0, 50)];
+        let samples = sample(1, &regions, 30.0, &|_| true);
+        assert!(!samples.is_empty());
+        assert!(samples.len() < 50);
+        assert!(sample(1, &[region(500.0, 400.0, 20.0, 0)], 30.0, &|_| true).is_empty());
+    }
+}
@@ -0,0 +1,283 @@
+// Wave progression read from a data table, e.g. `resources/waves.json`. Clearing the field
+// moves on to the next wave; once the table runs out the last wave repeats.
+use crate::archetype::{Archetype, ArchetypeParams};
+use ggez::{GameError, GameResult};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const DEFAULT_AGGRESSION: f32 = 1.0;
+
+// One row of the table. Anything left out keeps the level's own setting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wave {
+    pub count: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    // Names from the table's `archetypes`; collectibles are drawn from these at random
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archetypes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proximity_threshold: Option<f32>,
+    // Scales how far away collectibles notice the player and start reaching for it
+    #[serde(default = "default_aggression")]
+    pub aggression: f32,
+}
+
+fn default_aggression() -> f32 {
+    DEFAULT_AGGRESSION
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WaveTable {
+    #[serde(default)]
+    pub archetypes: Vec<ArchetypeParams>,
+    pub waves: Vec<Wave>,
+}
+
+impl WaveTable {
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<WaveTable> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| GameError::CustomError(format!("Can't read wave table {}: {}", path.display(), e)))?;
+        WaveTable::parse(&text, &path.display().to_string())
+    }
+
+    // `source` names where the text came from in error messages
+    pub fn parse(text: &str, source: &str) -> GameResult<WaveTable> {
+        let table: WaveTable = serde_json::from_str(text)
+            .map_err(|e| GameError::CustomError(format!("Invalid wave table {}: {}", source, e)))?;
+        table
+            .validate()
+            .map_err(|e| GameError::CustomError(format!("Invalid wave table {}: {}", source, e)))?;
+        Ok(table)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.waves.is_empty() {
+            return Err("there must be at least one wave".to_string());
+        }
+        for archetype in &self.archetypes {
+            archetype.validate()?;
+        }
+        for (i, wave) in self.waves.iter().enumerate() {
+            let number = i + 1;
+            // An empty wave would count as cleared straight away
+            if wave.count < 1 {
+                return Err(format!("wave {}: count must be at least 1, got {}", number, wave.count));
+            }
+            if wave.aggression.is_nan() || wave.aggression <= 0.0 {
+                return Err(format!("wave {}: aggression must be positive, got {}", number, wave.aggression));
+            }
+            if let Some(threshold) = wave.proximity_threshold {
+                if threshold.is_nan() || threshold <= 0.0 {
+                    return Err(format!(
+                        "wave {}: proximity_threshold must be positive, got {}",
+                        number, threshold
+                    ));
+                }
+            }
+            for name in &wave.archetypes {
+                if self.archetype(name).is_none() {
+                    return Err(format!("wave {}: unknown archetype '{}'", number, name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn archetype(&self, name: &str) -> Option<&ArchetypeParams> {
+        self.archetypes.iter().find(|archetype| archetype.name == name)
+    }
+
+    // A level and its wave table share one set of archetype names, so a name may only
+    // be defined in one of them
+    pub fn check_level_archetypes(&self, level_archetypes: &[ArchetypeParams]) -> Result<(), String> {
+        match self
+            .archetypes
+            .iter()
+            .find(|archetype| level_archetypes.iter().any(|other| other.name == archetype.name))
+        {
+            Some(archetype) => Err(format!(
+                "archetype '{}' is also defined by the level; rename one of them",
+                archetype.name
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Layouts are only looked up when a wave starts, so a misspelt name is caught up front
+    // against the layouts that are actually loaded
+    pub fn check_layouts(&self, layout_names: &[&str]) -> Result<(), String> {
+        for (i, wave) in self.waves.iter().enumerate() {
+            if let Some(layout) = &wave.layout {
+                if !layout_names.contains(&layout.as_str()) {
+                    return Err(format!(
+                        "wave {}: no cluster layout named '{}' (loaded: {})",
+                        i + 1,
+                        layout,
+                        layout_names.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Progression {
+    pub table: WaveTable,
+    // Zero-based; keeps counting past the end of the table
+    pub wave_index: usize,
+}
+
+impl Progression {
+    pub fn new(table: WaveTable) -> Self {
+        Progression { table, wave_index: 0 }
+    }
+
+    // One-based, for showing to the player
+    pub fn wave_number(&self) -> usize {
+        self.wave_index + 1
+    }
+
+    pub fn current(&self) -> &Wave {
+        let last = self.table.waves.len() - 1;
+        &self.table.waves[self.wave_index.min(last)]
+    }
+
+    pub fn advance(&mut self) {
+        self.wave_index += 1;
+    }
+
+    // The kinds of collectible this wave spawns, built on top of `base`
+    pub fn archetypes(&self, base: &Archetype) -> Vec<Archetype> {
+        let wave = self.current();
+        if wave.archetypes.is_empty() {
+            return vec![base.clone()];
+        }
+        wave.archetypes
+            .iter()
+            .filter_map(|name| self.table.archetype(name))
+            .map(|params| params.build(base))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster_layout::{self, ClusterLayout};
+    use crate::resources;
+
+    const TABLE: &str = r#"{
+        "archetypes": [ { "name": "brute", "hit_points": 5 } ],
+        "waves": [
+            { "count": 3 },
+            { "count": 5, "layout": "rows", "archetypes": ["brute"], "aggression": 1.5 }
+        ]
+    }"#;
+
+    fn parse_error(text: &str) -> String {
+        match WaveTable::parse(text, "waves.json") {
+            Err(GameError::CustomError(message)) => message,
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_a_table() {
+        let table = WaveTable::parse(TABLE, "waves.json").unwrap();
+        assert_eq!(table.waves.len(), 2);
+        assert_eq!(table.waves[0].aggression, DEFAULT_AGGRESSION);
+        assert_eq!(table.waves[1].layout.as_deref(), Some("rows"));
+        assert!(table.archetype("brute").is_some());
+    }
+
+    #[test]
+    fn rejects_unknown_archetypes() {
+        assert_eq!(
+            parse_error(r#"{ "waves": [ { "count": 2 }, { "count": 2, "archetypes": ["ghost"] } ] }"#),
+            "Invalid wave table waves.json: wave 2: unknown archetype 'ghost'"
+        );
+    }
+
+    #[test]
+    fn rejects_empty_waves() {
+        assert_eq!(
+            parse_error(r#"{ "waves": [ { "count": 0 } ] }"#),
+            "Invalid wave table waves.json: wave 1: count must be at least 1, got 0"
+        );
+        assert!(parse_error(r#"{ "waves": [] }"#).contains("at least one wave"));
+    }
+
+    #[test]
+    fn rejects_non_positive_aggression() {
+        assert_eq!(
+            parse_error(r#"{ "waves": [ { "count": 1, "aggression": 0 } ] }"#),
+            "Invalid wave table waves.json: wave 1: aggression must be positive, got 0"
+        );
+        assert!(parse_error(r#"{ "waves": [ { "count": 1, "aggression": -2 } ] }"#).contains("aggression must be positive"));
+    }
+
+    #[test]
+    fn last_wave_repeats_past_the_end() {
+        let mut progression = Progression::new(WaveTable::parse(TABLE, "waves.json").unwrap());
+        assert_eq!(progression.current().count, 3);
+        for _ in 0..4 {
+            progression.advance();
+        }
+        assert_eq!(progression.wave_number(), 5);
+        assert_eq!(progression.current(), &progression.table.waves[1]);
+    }
+
+    #[test]
+    fn waves_without_archetypes_use_the_base_one() {
+        let mut progression = Progression::new(WaveTable::parse(TABLE, "waves.json").unwrap());
+        let base = Archetype {
+            hit_points: Some(2),
+            ..Archetype::default()
+        };
+        assert_eq!(progression.archetypes(&base), vec![base.clone()]);
+
+        progression.advance();
+        let archetypes = progression.archetypes(&base);
+        assert_eq!(archetypes.len(), 1);
+        assert_eq!(archetypes[0].name, "brute");
+        assert_eq!(archetypes[0].hit_points, Some(5));
+    }
+
+    #[test]
+    fn level_and_table_archetypes_share_one_namespace() {
+        let table = WaveTable::parse(TABLE, "waves.json").unwrap();
+        let named = |name: &str| ArchetypeParams {
+            name: name.to_string(),
+            ..ArchetypeParams::default()
+        };
+        assert!(table.check_level_archetypes(&[named("keeper")]).is_ok());
+        assert_eq!(
+            table.check_level_archetypes(&[named("keeper"), named("brute")]),
+            Err("archetype 'brute' is also defined by the level; rename one of them".to_string())
+        );
+    }
+
+    #[test]
+    fn wave_layouts_must_be_loaded() {
+        let table = WaveTable::parse(TABLE, "waves.json").unwrap();
+        assert!(table.check_layouts(&["ring", "rows"]).is_ok());
+        assert_eq!(
+            table.check_layouts(&["ring", "row"]),
+            Err("wave 2: no cluster layout named 'rows' (loaded: ring, row)".to_string())
+        );
+    }
+
+    #[test]
+    fn shipped_table_names_shipped_layouts() {
+        let table = WaveTable::load(resources::resolve("resources/waves.json")).unwrap();
+        let layouts = ClusterLayout::load_dir(resources::resolve(cluster_layout::LAYOUTS_DIR)).unwrap();
+        let names: Vec<&str> = layouts.iter().map(|layout| layout.name.as_str()).collect();
+        assert_eq!(table.check_layouts(&names), Ok(()));
+    }
+}
@@ -0,0 +1,52 @@
+// Finds the game's data files no matter where it was started from. Relative paths are
+// looked up the way ggez finds its resource directory: next to Cargo.toml when run
+// through cargo, then next to the executable, and only then in the working directory.
+use std::path::{Path, PathBuf};
+
+pub const RESOURCES_DIR: &str = "resources";
+
+pub fn resolve<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    base_dirs()
+        .into_iter()
+        .map(|base| base.join(path))
+        .find(|candidate| candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+fn base_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(manifest_dir) = std::env::var("CARGO_MANIFEST_DIR") {
+        dirs.push(PathBuf::from(manifest_dir));
+    }
+    if let Some(exe_dir) = std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        dirs.push(exe_dir);
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_paths_are_kept() {
+        let path = Path::new("/nowhere/resources/waves.json");
+        assert_eq!(resolve(path), path);
+    }
+
+    #[test]
+    fn relative_paths_are_found_next_to_the_manifest() {
+        let resolved = resolve("resources/waves.json");
+        assert!(resolved.is_absolute());
+        assert!(resolved.exists());
+    }
+
+    #[test]
+    fn missing_files_are_left_for_the_caller_to_report() {
+        assert_eq!(resolve("resources/missing.json"), Path::new("resources/missing.json"));
+    }
+}
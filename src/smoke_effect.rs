@@ -25,8 +25,7 @@ impl SmokeEffect {
         }
     }
 
-    pub fn activate(&mut self, base_position: Point2<f32>) {
-        let mut rng = rand::thread_rng();
+    pub fn activate<R: Rng + ?Sized>(&mut self, base_position: Point2<f32>, color: Color, rng: &mut R) {
         let offset_x: f32 = rng.gen_range(-OFFSET_RANGE..OFFSET_RANGE);
         let offset_y: f32 = rng.gen_range(-OFFSET_RANGE..OFFSET_RANGE);
 
@@ -34,7 +33,7 @@ impl SmokeEffect {
             x: base_position.x + offset_x,
             y: base_position.y + offset_y,
         };
-        self.color = SMOKE_COLOR;
+        self.color = color;
         self.timer = 0.0; 
     }
 
@@ -0,0 +1,94 @@
+// Radial dents on a blob outline that spring back after being pressed.
+// One offset per outline point, matching the evenly spaced angles of the outline.
+
+pub const DEFAULT_STIFFNESS: f32 = 120.0;
+pub const DEFAULT_DAMPING: f32 = 8.0;
+pub const DEFAULT_PRESS_SPREAD: f32 = 0.9; // radians either side of the contact that get pushed in
+
+#[derive(Debug, Clone)]
+pub struct SoftBody {
+    // How far each outline point is pushed out (positive) or in (negative), in pixels
+    pub offsets: Vec<f32>,
+    velocities: Vec<f32>,
+    pub stiffness: f32,
+    pub damping: f32,
+    pub press_spread: f32,
+}
+
+impl SoftBody {
+    pub fn new(point_count: usize) -> Self {
+        SoftBody {
+            offsets: vec![0.0; point_count],
+            velocities: vec![0.0; point_count],
+            stiffness: DEFAULT_STIFFNESS,
+            damping: DEFAULT_DAMPING,
+            press_spread: DEFAULT_PRESS_SPREAD,
+        }
+    }
+
+    // Pushes the points around `contact_angle` inwards by up to `depth`, fading out
+    // towards the edge of the spread. Points already dented deeper are left alone.
+    pub fn press(&mut self, contact_angle: f32, depth: f32) {
+        let count = self.offsets.len();
+        if count == 0 || depth <= 0.0 || self.press_spread <= 0.0 {
+            return;
+        }
+        let angle_step = std::f32::consts::TAU / count as f32;
+        for i in 0..count {
+            let difference = angle_difference(i as f32 * angle_step, contact_angle).abs();
+            if difference >= self.press_spread {
+                continue;
+            }
+            let weight = 0.5 * (1.0 + (std::f32::consts::PI * difference / self.press_spread).cos());
+            let dent = -depth * weight;
+            if dent < self.offsets[i] {
+                self.offsets[i] = dent;
+                self.velocities[i] = 0.0;
+            }
+        }
+    }
+
+    // Springs every point back towards its rest position
+    pub fn update(&mut self, dt: f32) {
+        for (offset, velocity) in self.offsets.iter_mut().zip(self.velocities.iter_mut()) {
+            let acceleration = -self.stiffness * *offset - self.damping * *velocity;
+            *velocity += acceleration * dt;
+            *offset += *velocity * dt;
+        }
+    }
+}
+
+// Signed smallest difference between two angles, in -PI..=PI
+fn angle_difference(a: f32, b: f32) -> f32 {
+    let difference = (a - b).rem_euclid(std::f32::consts::TAU);
+    if difference > std::f32::consts::PI {
+        difference - std::f32::consts::TAU
+    } else {
+        difference
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn press_dents_around_the_contact() {
+        let mut body = SoftBody::new(16);
+        body.press(0.0, 5.0);
+        assert!((body.offsets[0] + 5.0).abs() < 1e-4);
+        assert!(body.offsets[1] < 0.0 && body.offsets[1] > body.offsets[0]);
+        assert!(body.offsets[15] < 0.0);
+        assert_eq!(body.offsets[8], 0.0);
+    }
+
+    #[test]
+    fn springs_return_to_rest() {
+        let mut body = SoftBody::new(16);
+        body.press(std::f32::consts::PI, 5.0);
+        for _ in 0..600 {
+            body.update(1.0 / 60.0);
+        }
+        assert!(body.offsets.iter().all(|offset| offset.abs() < 0.01), "{:?}", body.offsets);
+    }
+}
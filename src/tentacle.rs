@@ -1,20 +1,90 @@
+use crate::ik::{self, DEFAULT_IK_ITERATIONS, DEFAULT_IK_TOLERANCE};
+use easer::functions::{Easing, Sine};
 use ggez::{
-    graphics::{self, Color, DrawMode, MeshBuilder},
+    graphics::{self, Color, MeshBuilder, Vertex},
     Context, GameResult,
 };
 use mint::Point2;
 use nalgebra::{Norm, Vector2};
-use noise::{NoiseFn, Perlin};
+use noise::{NoiseFn, Perlin, Seedable};
+
+pub const DEFAULT_SEGMENT_COUNT: usize = 12;
+pub const DEFAULT_DRAG: f32 = 0.08;
+pub const CONSTRAINT_ITERATIONS: usize = 8;
+pub const TIP_DRIVE: f32 = 0.35; // fraction of the gap to its target the tip closes each step
+pub const WIGGLE_ACCELERATION: f32 = 40.0; // sideways push per unit of noise scale
+pub const MAX_STEP: f32 = 1.0 / 30.0; // longer frames are clamped to keep the rope stable
+pub const DEFAULT_MAX_JOINT_ANGLE: f32 = 0.6; // radians each joint may bend while reaching
+pub const REACH_BLEND_SPEED: f32 = 2.0; // how fast the tentacle switches between idling and reaching, per second
+pub const DEFAULT_TIP_TAPER: f32 = 0.15; // tip width as a fraction of the base width
+
+// One link of the rope. Velocity is implied by where the particle was last step.
+#[derive(Debug, Clone, Copy)]
+pub struct VerletParticle {
+    pub position: Vector2<f32>,
+    pub previous: Vector2<f32>,
+}
+
+impl VerletParticle {
+    pub fn at(position: Vector2<f32>) -> Self {
+        VerletParticle {
+            position,
+            previous: position,
+        }
+    }
+
+    pub fn integrate(&mut self, acceleration: Vector2<f32>, drag: f32, dt: f32) {
+        let velocity = (self.position - self.previous) * (1.0 - drag);
+        self.previous = self.position;
+        self.position += velocity + acceleration * dt * dt;
+    }
+}
+
+// Pulls neighbouring particles back to `rest_length` apart. The first particle is
+// treated as pinned and never moves.
+pub fn satisfy_constraints(particles: &mut [VerletParticle], rest_length: f32, iterations: usize) {
+    for _ in 0..iterations {
+        for i in 1..particles.len() {
+            let delta = particles[i].position - particles[i - 1].position;
+            let distance = delta.norm();
+            if distance == 0.0 {
+                continue;
+            }
+            let correction = delta * ((distance - rest_length) / distance);
+            if i == 1 {
+                particles[i].position -= correction;
+            } else {
+                particles[i - 1].position += correction * 0.5;
+                particles[i].position -= correction * 0.5;
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Tentacle {
     pub base_position: Point2<f32>,
+    // Width at the base; the ribbon narrows to `tip_thickness` at the tip
     pub thickness: f32,
+    pub tip_thickness: f32,
+    // Base color; the ribbon fades to `tip_color` at the tip
     pub color: Color,
+    pub tip_color: Color,
+    // Added to the shared time so sibling tentacles don't move in step
+    pub phase: f32,
     pub noise: Perlin,
+    pub noise_scale: f32,
     pub time: f64,
     pub points: Vec<Point2<f32>>,
     pub in_proximity: bool,
     current_tentacle_length: f32,
+    pub particles: Vec<VerletParticle>,
+    pub segment_count: usize,
+    pub gravity: Vector2<f32>,
+    pub drag: f32,
+    pub max_joint_angle: f32,
+    // 0.0 is pure idle rope motion, 1.0 is fully reaching for the target
+    reach_blend: f32,
 }
 
 impl Tentacle {
@@ -23,24 +93,37 @@ impl Tentacle {
         thickness: f32,
         color: Color,
         initial_time: f64,
+        noise_seed: u32,
+        noise_scale: f32,
     ) -> Self {
         Tentacle {
             base_position,
             thickness,
+            tip_thickness: thickness * DEFAULT_TIP_TAPER,
             color,
+            tip_color: color,
+            phase: 0.0,
             time: initial_time,
-            noise: Perlin::new(),
+            noise: Perlin::new().set_seed(noise_seed),
+            noise_scale,
             points: Vec::new(),
             in_proximity: false,
             current_tentacle_length: 1.0,
+            particles: Vec::new(),
+            segment_count: DEFAULT_SEGMENT_COUNT,
+            // Top-down, so nothing pulls the rope down by default
+            gravity: Vector2::new(0.0, 0.0),
+            drag: DEFAULT_DRAG,
+            max_joint_angle: DEFAULT_MAX_JOINT_ANGLE,
+            reach_blend: 0.0,
         }
     }
 
     pub fn update(
         &mut self,
         _ctx: &mut Context,
+        dt: f32,
         target_position: Point2<f32>,
-        _max_length: f32,
         normalized_distance: f32,
         t: f32,
         color: Color,
@@ -49,8 +132,7 @@ impl Tentacle {
     ) -> GameResult<()> {
         self.in_proximity = in_proximity;
         self.color = color;
-        self.time = t as f64; // time for noise evolution
-        self.points.clear();
+        self.time = (t + self.phase) as f64; // time for noise evolution
     
         let base_position = Vector2::new(self.base_position.x, self.base_position.y);
         let target_position = Vector2::new(target_position.x, target_position.y);
@@ -83,27 +165,95 @@ impl Tentacle {
             self.current_tentacle_length - (self.current_tentacle_length - desired_length) * 0.05 // Retract speed, adjust as needed
         };
     
-        // Generate points for the tentacle with noise
-        for i in 0..=self.current_tentacle_length as usize {
-            let along = i as f32 / self.current_tentacle_length; // Normalized position along tentacle
-            let noise_value = self.noise.get([self.time + along as f64 * 2.0, 0.0]) as f32;
-            let noise_offset = noise_value * 23.0; // Scale the noise effect
-    
-            // Calculate the vertex position with noise
-            let vertex = Point2 {
-                x: self.base_position.x + direction.x * i as f32 + perp_direction.x * noise_offset,
-                y: self.base_position.y + direction.y * i as f32 + perp_direction.y * noise_offset,
-            };
-            self.points.push(vertex);
-        }
+        self.simulate(dt.min(MAX_STEP), base_position, direction, perp_direction);
+
+        let blend_target = if in_proximity { 1.0 } else { 0.0 };
+        let blend_step = REACH_BLEND_SPEED * dt;
+        self.reach_blend += (blend_target - self.reach_blend).clamp(-blend_step, blend_step);
+
+        self.points = self.blend_with_reach(target_position, direction);
     
         Ok(())
     }
-    
+
+    // Solves the chain towards the target and mixes that pose into the idle rope.
+    // The chain is only as long as the current tentacle length, which caps the reach.
+    fn blend_with_reach(&self, target_position: Vector2<f32>, direction: Vector2<f32>) -> Vec<Point2<f32>> {
+        let idle: Vec<Vector2<f32>> = self.particles.iter().map(|particle| particle.position).collect();
+        let mut reaching = idle.clone();
+        if self.reach_blend > 0.0 {
+            ik::solve_fabrik(
+                &mut reaching,
+                self.current_tentacle_length / self.segment_count as f32,
+                target_position,
+                direction,
+                self.max_joint_angle,
+                DEFAULT_IK_ITERATIONS,
+                DEFAULT_IK_TOLERANCE,
+            );
+        }
+
+        let weight = Sine::ease_in_out(self.reach_blend, 0.0, 1.0, 1.0);
+        idle.iter()
+            .zip(reaching.iter())
+            .map(|(idle, reaching)| {
+                let blended = idle + (reaching - idle) * weight;
+                Point2 {
+                    x: blended.x,
+                    y: blended.y,
+                }
+            })
+            .collect()
+    }
+
+    // Steps the rope: every particle swings under drag and a noisy sideways push, the tip
+    // is steered out to the current length, and the links are pulled back into shape.
+    fn simulate(
+        &mut self,
+        dt: f32,
+        base_position: Vector2<f32>,
+        direction: Vector2<f32>,
+        perp_direction: Vector2<f32>,
+    ) {
+        if self.particles.len() != self.segment_count + 1 {
+            self.particles = vec![VerletParticle::at(base_position); self.segment_count + 1];
+        }
+        let rest_length = self.current_tentacle_length / self.segment_count as f32;
+
+        for (i, particle) in self.particles.iter_mut().enumerate().skip(1) {
+            let along = i as f32 / self.segment_count as f32; // Normalized position along tentacle
+            let noise_value = self.noise.get([self.time + along as f64 * 2.0, 0.0]) as f32;
+            let wiggle = perp_direction * (noise_value * self.noise_scale * WIGGLE_ACCELERATION);
+            particle.integrate(self.gravity + wiggle, self.drag, dt);
+        }
+
+        // Idle wiggle on the tip as well, so it doesn't just point straight out
+        let noise_value = self.noise.get([self.time + 2.0, 0.0]) as f32;
+        let tip_target = base_position
+            + direction * self.current_tentacle_length
+            + perp_direction * (noise_value * self.noise_scale);
+        if let Some(tip) = self.particles.last_mut() {
+            tip.position += (tip_target - tip.position) * TIP_DRIVE;
+        }
+
+        self.particles[0] = VerletParticle::at(base_position);
+        satisfy_constraints(&mut self.particles, rest_length, CONSTRAINT_ITERATIONS);
+    }
+
     pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.points.len() < 2 {
+            return Ok(());
+        }
         // Build the tentacle mesh from the points
+        let (vertices, indices) = ribbon(
+            &self.points,
+            self.thickness,
+            self.tip_thickness,
+            self.color,
+            self.tip_color,
+        );
         let tentacle_mesh = MeshBuilder::new()
-            .polyline(DrawMode::stroke(self.thickness), &self.points, self.color)?
+            .raw(&vertices, &indices, None)?
             .build(ctx)?;
 
         graphics::draw(ctx, &tentacle_mesh, graphics::DrawParam::default())?;
@@ -118,3 +268,119 @@ impl Tentacle {
         self.thickness = new_thickness;
     }
 }
+
+// Turns a center line into a strip of triangles that narrows and changes color from
+// base to tip. Every point gets two vertices, one on either side of the line.
+// Needs at least two points to produce anything.
+pub fn ribbon(
+    points: &[Point2<f32>],
+    base_width: f32,
+    tip_width: f32,
+    base_color: Color,
+    tip_color: Color,
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(points.len() * 2);
+    let mut indices = Vec::with_capacity(points.len().saturating_sub(1) * 6);
+    if points.len() < 2 {
+        return (vertices, indices);
+    }
+
+    let last = points.len() - 1;
+    for (i, point) in points.iter().enumerate() {
+        // Tangent from the neighbours on either side so the joints stay smooth
+        let before = points[i.saturating_sub(1)];
+        let after = points[(i + 1).min(last)];
+        let tangent = Vector2::new(after.x - before.x, after.y - before.y);
+        let length = tangent.norm();
+        let normal = if length > f32::EPSILON {
+            Vector2::new(-tangent.y / length, tangent.x / length)
+        } else {
+            Vector2::new(0.0, 0.0)
+        };
+
+        let along = i as f32 / last as f32;
+        let half_width = (base_width + (tip_width - base_width) * along) / 2.0;
+        let color = [
+            base_color.r + (tip_color.r - base_color.r) * along,
+            base_color.g + (tip_color.g - base_color.g) * along,
+            base_color.b + (tip_color.b - base_color.b) * along,
+            base_color.a + (tip_color.a - base_color.a) * along,
+        ];
+        for side in [1.0, -1.0] {
+            vertices.push(Vertex {
+                pos: [
+                    point.x + normal.x * half_width * side,
+                    point.y + normal.y * half_width * side,
+                ],
+                uv: [along, (side + 1.0) / 2.0],
+                color,
+            });
+        }
+
+        if i < last {
+            let left = (i * 2) as u32;
+            indices.extend_from_slice(&[left, left + 1, left + 2, left + 1, left + 3, left + 2]);
+        }
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ribbon_tapers_from_base_to_tip() {
+        let points: Vec<Point2<f32>> = (0..5).map(|i| Point2 { x: i as f32 * 10.0, y: 0.0 }).collect();
+        let (vertices, indices) = ribbon(&points, 10.0, 2.0, Color::WHITE, Color::BLACK);
+        assert_eq!(vertices.len(), points.len() * 2);
+        assert_eq!(indices.len(), (points.len() - 1) * 6);
+
+        let widths: Vec<f32> = vertices
+            .chunks(2)
+            .map(|pair| {
+                let (a, b) = (pair[0].pos, pair[1].pos);
+                ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+            })
+            .collect();
+        assert!((widths[0] - 10.0).abs() < 1e-4);
+        assert!((widths[4] - 2.0).abs() < 1e-4);
+        assert!(widths.windows(2).all(|pair| pair[1] < pair[0]));
+    }
+
+    fn stretched_rope(count: usize) -> Vec<VerletParticle> {
+        (0..count)
+            .map(|i| VerletParticle::at(Vector2::new(i as f32 * 30.0, (i % 2) as f32 * 20.0)))
+            .collect()
+    }
+
+    #[test]
+    fn segments_settle_at_rest_length() {
+        let mut particles = stretched_rope(6);
+        satisfy_constraints(&mut particles, 10.0, 200);
+        for pair in particles.windows(2) {
+            let length = (pair[1].position - pair[0].position).norm();
+            assert!((length - 10.0).abs() < 1e-3, "segment length {}", length);
+        }
+    }
+
+    #[test]
+    fn first_particle_stays_pinned() {
+        let mut particles = stretched_rope(6);
+        let base = particles[0].position;
+        for _ in 0..10 {
+            for particle in particles.iter_mut().skip(1) {
+                particle.integrate(Vector2::new(0.0, 500.0), DEFAULT_DRAG, MAX_STEP);
+            }
+            satisfy_constraints(&mut particles, 10.0, CONSTRAINT_ITERATIONS);
+        }
+        assert_eq!(particles[0].position, base);
+    }
+
+    #[test]
+    fn ribbon_needs_two_points() {
+        let (vertices, indices) = ribbon(&[Point2 { x: 0.0, y: 0.0 }], 10.0, 2.0, Color::WHITE, Color::BLACK);
+        assert!(vertices.is_empty());
+        assert!(indices.is_empty());
+    }
+}
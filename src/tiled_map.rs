@@ -0,0 +1,814 @@
+// Imports maps made in Tiled (https://www.mapeditor.org) and saved as JSON, e.g.
+// `resources/maps/garden.tmj`. Only finite, orthogonal maps with CSV tile data are read.
+//
+// Objects are sorted by their class (saved as `type`, or `class` by Tiled 1.9):
+//   spawn        where the player starts; a map has exactly one
+//   collectible  a collectible at the object's center
+//   cluster      a cluster center; the object's size, or a `spread` property, is the spread
+//   obstacle     a solid rectangle
+//   exclusion    a rectangle or ellipse generated collectibles stay out of
+// Objects without a class are notes and are skipped.
+//
+// Custom properties on a collectible are archetype parameters (`hit_points`, `split_scale`, ...)
+// and `archetype` names the kind, so other collectibles can ask for it with just that property.
+// Properties on the map itself fill in the rest of the level: `name`, `collectible_size`,
+// `hit_points`, `proximity_threshold`, `min_spawn_distance`, `count`, `cluster_size` and `waves`.
+//
+// Tile layers aren't part of the level; they're drawn as its background by `TileBackground`.
+use crate::archetype::ArchetypeParams;
+use crate::cluster_layout::ClusterSpec;
+use crate::level::{
+    CollectibleSettings, GeneratorParams, Level, Placement, Position, UiAnchors, WorldSize,
+    COLLECTIBLES_PER_CLUSTER, DEFAULT_CLUSTER_SIZE, DEFAULT_PROXIMITY_THRESHOLD, LEVEL_FORMAT_VERSION,
+};
+use crate::obstacle::Obstacle;
+use crate::placement_constraints::{ExclusionZone, DEFAULT_MIN_SPAWN_DISTANCE};
+use crate::resources::RESOURCES_DIR;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color, DrawParam, FilterMode, Image, Rect};
+use ggez::{Context, GameError, GameResult};
+use mint::Point2;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::path::{Component, Path, PathBuf};
+
+pub const MAP_EXTENSION: &str = "tmj";
+pub const DEFAULT_COLLECTIBLE_SIZE: f32 = 80.0;
+// The top bits of a tile id say how the tile is flipped
+const GID_FLAGS: u32 = 0xE000_0000;
+
+// The parts of a Tiled map this game uses; Tiled writes plenty more, which is ignored
+#[derive(Debug, Clone, Deserialize)]
+pub struct TiledMap {
+    pub width: u32,
+    pub height: u32,
+    pub tilewidth: u32,
+    pub tileheight: u32,
+    #[serde(default)]
+    pub orientation: String,
+    #[serde(default)]
+    pub infinite: bool,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub tilesets: Vec<TilesetRef>,
+    #[serde(default)]
+    pub properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum Layer {
+    #[serde(rename = "tilelayer")]
+    Tiles(TileLayer),
+    #[serde(rename = "objectgroup")]
+    Objects(ObjectLayer),
+    #[serde(rename = "group")]
+    Group(GroupLayer),
+    // Image layers and anything newer
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileLayer {
+    #[serde(default)]
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub data: TileData,
+    // `base64` for layers not saved as plain lists of tile ids
+    #[serde(default)]
+    pub encoding: Option<String>,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    #[serde(default)]
+    pub offsetx: f32,
+    #[serde(default)]
+    pub offsety: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum TileData {
+    Ids(Vec<u32>),
+    // Anything else, e.g. base64; `encoding` tells which
+    Unsupported(IgnoredAny),
+}
+
+impl Default for TileData {
+    fn default() -> Self {
+        TileData::Ids(Vec::new())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObjectLayer {
+    #[serde(default)]
+    pub objects: Vec<Object>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupLayer {
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Object {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub class: String,
+    #[serde(default, rename = "type")]
+    pub legacy_type: String,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub width: f32,
+    #[serde(default)]
+    pub height: f32,
+    #[serde(default)]
+    pub point: bool,
+    #[serde(default)]
+    pub ellipse: bool,
+    #[serde(default)]
+    pub rotation: f32,
+    // Set on tile objects, which Tiled anchors at their bottom left corner
+    #[serde(default)]
+    pub gid: Option<u32>,
+    #[serde(default)]
+    pub polygon: Option<Value>,
+    #[serde(default)]
+    pub polyline: Option<Value>,
+    #[serde(default)]
+    pub properties: Vec<Property>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Property {
+    pub name: String,
+    #[serde(default, rename = "type")]
+    pub kind: String,
+    pub value: Value,
+}
+
+// A tileset as listed in the map: either embedded, or in a `.tsj` file of its own
+#[derive(Debug, Clone, Deserialize)]
+pub struct TilesetRef {
+    pub firstgid: u32,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(flatten)]
+    pub embedded: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tileset {
+    #[serde(default)]
+    pub name: String,
+    // Missing for image collection tilesets, which aren't supported
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(default)]
+    pub imagewidth: u32,
+    #[serde(default)]
+    pub imageheight: u32,
+    pub tilewidth: u32,
+    pub tileheight: u32,
+    #[serde(default)]
+    pub columns: u32,
+    #[serde(default)]
+    pub margin: u32,
+    #[serde(default)]
+    pub spacing: u32,
+}
+
+// Map properties that set up the level
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MapProperties {
+    name: Option<String>,
+    collectible_size: Option<f32>,
+    hit_points: Option<u32>,
+    proximity_threshold: Option<f32>,
+    min_spawn_distance: Option<f32>,
+    count: Option<i32>,
+    cluster_size: Option<f32>,
+    waves: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ClusterProperties {
+    spread: Option<f32>,
+    count: Option<u32>,
+    weight: Option<f32>,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+// Reads a Tiled map as a level; its tile layers become the level's background
+pub fn import<P: AsRef<Path>>(path: P) -> GameResult<Level> {
+    let path = path.as_ref();
+    let source = path.display().to_string();
+    let map = TiledMap::load(path)?;
+    let level = map
+        .to_level(path)
+        .map_err(|e| GameError::CustomError(format!("Invalid Tiled map {}: {}", source, e)))?;
+    level
+        .validate()
+        .map_err(|e| GameError::CustomError(format!("Invalid level {}: {}", source, e)))?;
+    Ok(level)
+}
+
+impl TiledMap {
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<TiledMap> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| GameError::CustomError(format!("Can't read Tiled map {}: {}", path.display(), e)))?;
+        TiledMap::parse(&text, &path.display().to_string())
+    }
+
+    // `source` names where the text came from in error messages
+    pub fn parse(text: &str, source: &str) -> GameResult<TiledMap> {
+        let map: TiledMap = serde_json::from_str(text)
+            .map_err(|e| GameError::CustomError(format!("Invalid Tiled map {}: {}", source, e)))?;
+        map.validate()
+            .map_err(|e| GameError::CustomError(format!("Invalid Tiled map {}: {}", source, e)))?;
+        Ok(map)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.orientation.is_empty() && self.orientation != "orthogonal" {
+            return Err(format!("only orthogonal maps are supported, this one is {}", self.orientation));
+        }
+        if self.infinite {
+            return Err("infinite maps are not supported".to_string());
+        }
+        if self.width == 0 || self.height == 0 || self.tilewidth == 0 || self.tileheight == 0 {
+            return Err("map and tile sizes must be positive".to_string());
+        }
+        for layer in self.tile_layers() {
+            match &layer.data {
+                TileData::Unsupported(_) => {
+                    return Err(match &layer.encoding {
+                        Some(encoding) => format!(
+                            "tile layer '{}' is saved as {}; save the map with the CSV tile layer format",
+                            layer.name, encoding
+                        ),
+                        None => format!("tile layer '{}' has data that isn't a list of tile ids", layer.name),
+                    })
+                }
+                TileData::Ids(ids) if ids.len() != (layer.width * layer.height) as usize => {
+                    return Err(format!(
+                        "tile layer '{}' has {} tiles, expected {}x{}",
+                        layer.name,
+                        ids.len(),
+                        layer.width,
+                        layer.height
+                    ))
+                }
+                TileData::Ids(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn world_size(&self) -> WorldSize {
+        WorldSize {
+            width: (self.width * self.tilewidth) as f32,
+            height: (self.height * self.tileheight) as f32,
+        }
+    }
+
+    // Visible tile layers, bottom to top, with groups flattened
+    pub fn tile_layers(&self) -> Vec<&TileLayer> {
+        let mut found = Vec::new();
+        collect_tile_layers(&self.layers, &mut found);
+        found
+    }
+
+    pub fn objects(&self) -> Vec<&Object> {
+        let mut found = Vec::new();
+        collect_objects(&self.layers, &mut found);
+        found
+    }
+
+    // Every tileset with its first tile id, external ones read from next to `map_path`,
+    // along with the file its image path is relative to
+    pub fn tilesets(&self, map_path: &Path) -> GameResult<Vec<(u32, Tileset, PathBuf)>> {
+        let mut tilesets = Vec::with_capacity(self.tilesets.len());
+        for tileset in &self.tilesets {
+            let (parsed, relative_to) = match &tileset.source {
+                Some(source) => {
+                    let path = sibling(map_path, source);
+                    let text = std::fs::read_to_string(&path).map_err(|e| {
+                        GameError::CustomError(format!("Can't read tileset {}: {}", path.display(), e))
+                    })?;
+                    let parsed: Tileset = serde_json::from_str(&text).map_err(|e| {
+                        GameError::CustomError(format!("Invalid tileset {}: {}", path.display(), e))
+                    })?;
+                    (parsed, path)
+                }
+                None => {
+                    let parsed: Tileset = serde_json::from_value(Value::Object(tileset.embedded.clone()))
+                        .map_err(|e| {
+                            GameError::CustomError(format!(
+                                "Invalid tileset in {}: {}",
+                                map_path.display(),
+                                e
+                            ))
+                        })?;
+                    (parsed, map_path.to_path_buf())
+                }
+            };
+            tilesets.push((tileset.firstgid, parsed, relative_to));
+        }
+        // Lookups go by the highest first id at or below a tile's id
+        tilesets.sort_by_key(|(firstgid, _, _)| *firstgid);
+        Ok(tilesets)
+    }
+
+    pub fn to_level(&self, path: &Path) -> Result<Level, String> {
+        let properties: MapProperties = from_properties(&self.properties).map_err(|e| format!("map properties: {}", e))?;
+        let world = self.world_size();
+        let mut spawn: Option<Position> = None;
+        let mut obstacles = Vec::new();
+        let mut exclusion_zones = Vec::new();
+        let mut clusters = Vec::new();
+        let mut placements = Vec::new();
+        let mut archetypes: Vec<ArchetypeParams> = Vec::new();
+
+        for object in self.objects() {
+            let class = object.class();
+            if class.is_empty() {
+                continue;
+            }
+            let what = format!("object {} ({})", object.id, class);
+            if object.polygon.is_some() || object.polyline.is_some() {
+                return Err(format!("{}: polygons and polylines are not supported", what));
+            }
+            if object.rotation != 0.0 && (class == "obstacle" || class == "exclusion") {
+                return Err(format!("{}: rotated shapes are not supported", what));
+            }
+            let center = object.center();
+            match class {
+                "spawn" => {
+                    if spawn.is_some() {
+                        return Err(format!("{}: the map already has a spawn", what));
+                    }
+                    spawn = Some(Position { x: center.x, y: center.y });
+                }
+                "collectible" => {
+                    let archetype = collectible_archetype(object, &mut archetypes).map_err(|e| format!("{}: {}", what, e))?;
+                    placements.push(Placement {
+                        x: center.x,
+                        y: center.y,
+                        archetype,
+                    });
+                }
+                "cluster" => {
+                    let cluster: ClusterProperties =
+                        from_properties(&object.properties).map_err(|e| format!("{}: {}", what, e))?;
+                    let size_spread = (object.width > 0.0).then(|| object.width.max(object.height) / 2.0);
+                    clusters.push(ClusterSpec {
+                        x: center.x / world.width,
+                        y: center.y / world.height,
+                        spread: cluster.spread.or(size_spread),
+                        count: cluster.count,
+                        weight: cluster.weight.unwrap_or(1.0),
+                    });
+                }
+                "obstacle" => {
+                    if object.point || object.ellipse {
+                        return Err(format!("{}: obstacles must be rectangles", what));
+                    }
+                    let top_left = object.top_left();
+                    obstacles.push(Obstacle {
+                        x: top_left.x,
+                        y: top_left.y,
+                        width: object.width,
+                        height: object.height,
+                    });
+                }
+                "exclusion" => {
+                    let top_left = object.top_left();
+                    exclusion_zones.push(match (object.point, object.ellipse) {
+                        (true, _) => return Err(format!("{}: exclusion zones need a size", what)),
+                        // Ellipses are read as the circle that covers them
+                        (false, true) => ExclusionZone::Circle {
+                            x: center.x,
+                            y: center.y,
+                            radius: object.width.max(object.height) / 2.0,
+                        },
+                        (false, false) => ExclusionZone::Rect {
+                            x: top_left.x,
+                            y: top_left.y,
+                            width: object.width,
+                            height: object.height,
+                        },
+                    });
+                }
+                _ => return Err(format!("{}: unknown class '{}'", what, class)),
+            }
+        }
+
+        let player_spawn = spawn.ok_or_else(|| "the map has no object of class 'spawn'".to_string())?;
+        let generate = if clusters.is_empty() {
+            None
+        } else {
+            Some(GeneratorParams {
+                layout: None,
+                count: properties
+                    .count
+                    .unwrap_or(clusters.len() as i32 * COLLECTIBLES_PER_CLUSTER),
+                clusters,
+                cluster_size: properties.cluster_size.unwrap_or(DEFAULT_CLUSTER_SIZE),
+                density: None,
+            })
+        };
+        let name = properties.name.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map_or_else(|| "tiled".to_string(), |stem| stem.to_string_lossy().into_owned())
+        });
+
+        Ok(Level {
+            version: LEVEL_FORMAT_VERSION,
+            name,
+            world,
+            player_spawn,
+            obstacles,
+            exclusion_zones,
+            collectibles: CollectibleSettings {
+                size: properties.collectible_size.unwrap_or(DEFAULT_COLLECTIBLE_SIZE),
+                hit_points: properties.hit_points,
+                proximity_threshold: properties.proximity_threshold.unwrap_or(DEFAULT_PROXIMITY_THRESHOLD),
+                min_spawn_distance: properties.min_spawn_distance.unwrap_or(DEFAULT_MIN_SPAWN_DISTANCE),
+                generate,
+                placements,
+            },
+            archetypes,
+            background: (!self.tile_layers().is_empty()).then(|| path.display().to_string()),
+            waves: self.waves_path(path, &properties),
+            ui: UiAnchors::default(),
+        })
+    }
+
+    // A `file` property is relative to the map, like Tiled shows it; a plain string is
+    // relative to the game directory, like paths in level files
+    fn waves_path(&self, path: &Path, properties: &MapProperties) -> Option<String> {
+        let waves = properties.waves.as_ref()?;
+        let is_file = self
+            .properties
+            .iter()
+            .any(|property| property.name == "waves" && property.kind == "file");
+        if is_file {
+            Some(sibling(path, waves).display().to_string())
+        } else {
+            Some(waves.clone())
+        }
+    }
+}
+
+impl Object {
+    // Tiled 1.9 saved the class as `class`; other versions use `type`
+    pub fn class(&self) -> &str {
+        if self.class.is_empty() {
+            &self.legacy_type
+        } else {
+            &self.class
+        }
+    }
+
+    pub fn top_left(&self) -> Point2<f32> {
+        let y = if self.gid.is_some() { self.y - self.height } else { self.y };
+        Point2 { x: self.x, y }
+    }
+
+    pub fn center(&self) -> Point2<f32> {
+        let top_left = self.top_left();
+        Point2 {
+            x: top_left.x + self.width / 2.0,
+            y: top_left.y + self.height / 2.0,
+        }
+    }
+}
+
+// The archetype a collectible object asks for. Properties other than `archetype` define it;
+// it's named by `archetype`, the object's name or its id, in that order.
+fn collectible_archetype(object: &Object, archetypes: &mut Vec<ArchetypeParams>) -> Result<Option<String>, String> {
+    let mut fields = property_map(&object.properties);
+    let named = match fields.remove("archetype") {
+        Some(Value::String(name)) => Some(name),
+        Some(other) => return Err(format!("`archetype` must be a string, got {}", other)),
+        None => None,
+    };
+    if fields.is_empty() {
+        return Ok(named);
+    }
+
+    let name = named
+        .or_else(|| (!object.name.is_empty()).then(|| object.name.clone()))
+        .unwrap_or_else(|| format!("object{}", object.id));
+    fields.insert("name".to_string(), Value::String(name.clone()));
+    let params: ArchetypeParams = serde_json::from_value(Value::Object(fields)).map_err(|e| e.to_string())?;
+    match archetypes.iter().find(|known| known.name == name) {
+        Some(known) if *known != params => {
+            return Err(format!("archetype '{}' is given different properties elsewhere", name))
+        }
+        Some(_) => {}
+        None => archetypes.push(params),
+    }
+    Ok(Some(name))
+}
+
+fn from_properties<T: DeserializeOwned>(properties: &[Property]) -> Result<T, String> {
+    serde_json::from_value(Value::Object(property_map(properties))).map_err(|e| e.to_string())
+}
+
+fn property_map(properties: &[Property]) -> Map<String, Value> {
+    properties
+        .iter()
+        .map(|property| (property.name.clone(), property.value.clone()))
+        .collect()
+}
+
+fn collect_tile_layers<'a>(layers: &'a [Layer], found: &mut Vec<&'a TileLayer>) {
+    for layer in layers {
+        match layer {
+            Layer::Tiles(tiles) if tiles.visible => found.push(tiles),
+            Layer::Group(group) if group.visible => collect_tile_layers(&group.layers, found),
+            _ => {}
+        }
+    }
+}
+
+// Objects count even on hidden layers; hiding a layer in Tiled is just for editing
+fn collect_objects<'a>(layers: &'a [Layer], found: &mut Vec<&'a Object>) {
+    for layer in layers {
+        match layer {
+            Layer::Objects(objects) => found.extend(objects.objects.iter()),
+            Layer::Group(group) => collect_objects(&group.layers, found),
+            _ => {}
+        }
+    }
+}
+
+// `relative` resolved against the directory `file` is in
+fn sibling(file: &Path, relative: &str) -> PathBuf {
+    file.parent().unwrap_or_else(|| Path::new("")).join(relative)
+}
+
+// ggez looks for images in the resources directory, so `resources/tiles/a.png`
+// (or `resources/maps/../tiles/a.png`, or `/home/me/top/resources/tiles/a.png`) has to be
+// asked for as `/tiles/a.png`
+fn resource_path(path: &Path) -> GameResult<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+    // The innermost `resources` directory, wherever the path starts
+    let rest = parts.iter().rposition(|part| part == RESOURCES_DIR).map(|i| &parts[i + 1..]);
+    match rest {
+        Some(rest) if !rest.is_empty() => Ok(format!("/{}", rest.join("/"))),
+        _ => Err(GameError::CustomError(format!(
+            "Tileset image {} must be inside the {} directory",
+            path.display(),
+            RESOURCES_DIR
+        ))),
+    }
+}
+
+// A map's tile layers, ready to draw: one sprite batch per layer and tileset
+pub struct TileBackground {
+    batches: Vec<SpriteBatch>,
+}
+
+impl TileBackground {
+    pub fn load<P: AsRef<Path>>(ctx: &mut Context, path: P) -> GameResult<TileBackground> {
+        let path = path.as_ref();
+        let map = TiledMap::load(path)?;
+        let tilesets = map.tilesets(path)?;
+        let mut images = Vec::with_capacity(tilesets.len());
+        for (_, tileset, relative_to) in &tilesets {
+            let file = tileset.image.as_ref().ok_or_else(|| {
+                GameError::CustomError(format!(
+                    "Tileset '{}' in {} is an image collection, which is not supported",
+                    tileset.name,
+                    path.display()
+                ))
+            })?;
+            let mut image = Image::new(ctx, resource_path(&sibling(relative_to, file))?)?;
+            // Keep pixel art crisp when the window is scaled
+            image.set_filter(FilterMode::Nearest);
+            images.push(image);
+        }
+
+        let mut batches = Vec::new();
+        for layer in map.tile_layers() {
+            let ids = match &layer.data {
+                TileData::Ids(ids) => ids,
+                TileData::Unsupported(_) => continue,
+            };
+            let mut layer_batches: Vec<Option<SpriteBatch>> = tilesets.iter().map(|_| None).collect();
+            for (i, &gid) in ids.iter().enumerate() {
+                // Flipped tiles are drawn unflipped
+                let gid = gid & !GID_FLAGS;
+                let index = match tilesets.iter().rposition(|(firstgid, _, _)| *firstgid <= gid) {
+                    Some(index) if gid != 0 => index,
+                    _ => continue,
+                };
+                let (firstgid, tileset, _) = &tilesets[index];
+                let columns = tileset.columns.max(1);
+                let local = gid - firstgid;
+                let column = i as u32 % layer.width;
+                let row = i as u32 / layer.width;
+                let src_x = tileset.margin + (local % columns) * (tileset.tilewidth + tileset.spacing);
+                let src_y = tileset.margin + (local / columns) * (tileset.tileheight + tileset.spacing);
+                let image_width = tileset.imagewidth.max(1) as f32;
+                let image_height = tileset.imageheight.max(1) as f32;
+                let batch = layer_batches[index].get_or_insert_with(|| SpriteBatch::new(images[index].clone()));
+                batch.add(
+                    DrawParam::new()
+                        .src(Rect::new(
+                            src_x as f32 / image_width,
+                            src_y as f32 / image_height,
+                            tileset.tilewidth as f32 / image_width,
+                            tileset.tileheight as f32 / image_height,
+                        ))
+                        // Tiles taller than the grid stick out upwards, as in Tiled
+                        .dest(Point2 {
+                            x: (column * map.tilewidth) as f32 + layer.offsetx,
+                            y: ((row + 1) * map.tileheight) as f32 - tileset.tileheight as f32 + layer.offsety,
+                        })
+                        .color(Color::new(1.0, 1.0, 1.0, layer.opacity)),
+                );
+            }
+            batches.extend(layer_batches.into_iter().flatten());
+        }
+        Ok(TileBackground { batches })
+    }
+
+    pub fn draw(&self, ctx: &mut Context) -> GameResult<()> {
+        for batch in &self.batches {
+            graphics::draw(ctx, batch, DrawParam::default())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progression::WaveTable;
+
+    const GARDEN: &str = "resources/maps/garden.tmj";
+    const RUINS: &str = "resources/maps/ruins.tmj";
+
+    // A 4x4 map with a spawn, plus whatever layers and objects a test adds
+    fn small_map(layers: &str, objects: &str) -> String {
+        format!(
+            r#"{{
+                "width": 4, "height": 4, "tilewidth": 32, "tileheight": 32, "orientation": "orthogonal",
+                "layers": [
+                    {layers}
+                    {{ "type": "objectgroup", "objects": [
+                        {{ "id": 1, "type": "spawn", "x": 16, "y": 16, "point": true }}
+                        {objects}
+                    ] }}
+                ]
+            }}"#,
+            layers = layers,
+            objects = objects
+        )
+    }
+
+    fn parse_error(text: &str) -> String {
+        match TiledMap::parse(text, "test.tmj") {
+            Err(GameError::CustomError(message)) => message,
+            other => panic!("expected an error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    fn level_error(objects: &str) -> String {
+        let map = TiledMap::parse(&small_map("", objects), "test.tmj").unwrap();
+        map.to_level(Path::new("test.tmj")).unwrap_err()
+    }
+
+    #[test]
+    fn imports_the_garden() {
+        let map = TiledMap::load(GARDEN).unwrap();
+        let level = map.to_level(Path::new(GARDEN)).unwrap();
+        assert_eq!(level.name, "garden");
+        assert_eq!((level.player_spawn.x, level.player_spawn.y), (640.0, 736.0));
+        assert_eq!(level.obstacles.len(), 2);
+        assert_eq!(level.collectibles.size, 80.0);
+
+        let generate = level.collectibles.generate.as_ref().unwrap();
+        assert_eq!(generate.count, 10);
+        assert_eq!(generate.cluster_size, 150.0);
+        assert_eq!(generate.clusters.len(), 3);
+        // The ellipses' size is their spread; the point cluster says so itself
+        assert_eq!(generate.clusters[0].spread, Some(120.0));
+        assert_eq!(generate.clusters[1].weight, 2.0);
+        assert_eq!(generate.clusters[2].spread, Some(120.0));
+        assert_eq!(generate.clusters[2].count, Some(2));
+
+        // Both brutes share one archetype; the unnamed collectible is named by its id
+        let placed: Vec<Option<&str>> = level.collectibles.placements.iter().map(|p| p.archetype.as_deref()).collect();
+        assert_eq!(placed, vec![Some("brute"), Some("brute"), Some("object9")]);
+        let names: Vec<&str> = level.archetypes.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["brute", "object9"]);
+        assert!(level.validate().is_ok());
+    }
+
+    #[test]
+    fn imports_the_ruins() {
+        let map = TiledMap::load(RUINS).unwrap();
+        let level = map.to_level(Path::new(RUINS)).unwrap();
+        assert_eq!(level.name, "ruins");
+        // A `file` property is relative to the map
+        assert_eq!(level.waves.as_deref(), Some("resources/maps/../waves.json"));
+        assert_eq!(level.obstacles.len(), 6);
+        assert_eq!(
+            level.exclusion_zones[0],
+            ExclusionZone::Circle {
+                x: 640.0,
+                y: 400.0,
+                radius: 80.0
+            }
+        );
+        assert!(matches!(level.exclusion_zones[1], ExclusionZone::Rect { .. }));
+        assert_eq!(level.collectibles.placements.len(), 5);
+        assert!(level.validate().is_ok());
+
+        // The keeper mustn't clash with anything the wave table defines
+        let table = WaveTable::load(level.waves.as_ref().unwrap()).unwrap();
+        assert!(table.check_level_archetypes(&level.archetypes).is_ok());
+    }
+
+    #[test]
+    fn rejects_maps_it_cannot_read() {
+        let base64 = r#"{ "type": "tilelayer", "name": "ground", "width": 4, "height": 4,
+                          "data": "AAAAAA==", "encoding": "base64" },"#;
+        assert!(parse_error(&small_map(base64, "")).contains("save the map with the CSV tile layer format"));
+
+        let short = r#"{ "type": "tilelayer", "name": "ground", "width": 4, "height": 4, "data": [1, 2] },"#;
+        assert!(parse_error(&small_map(short, "")).contains("has 2 tiles, expected 4x4"));
+
+        let infinite = small_map("", "").replacen("\"width\": 4,", "\"infinite\": true, \"width\": 4,", 1);
+        assert!(parse_error(&infinite).contains("infinite maps are not supported"));
+    }
+
+    #[test]
+    fn rejects_objects_it_cannot_import() {
+        let second_spawn = r#", { "id": 2, "type": "spawn", "x": 48, "y": 48, "point": true }"#;
+        assert!(level_error(second_spawn).contains("the map already has a spawn"));
+
+        let polygon = r#", { "id": 2, "type": "obstacle", "x": 0, "y": 0, "polygon": [{ "x": 0, "y": 0 }] }"#;
+        assert!(level_error(polygon).contains("polygons and polylines are not supported"));
+
+        let unknown = r#", { "id": 2, "type": "teleporter", "x": 0, "y": 0, "width": 32, "height": 32 }"#;
+        assert!(level_error(unknown).contains("unknown class 'teleporter'"));
+
+        let conflicting = r#",
+            { "id": 2, "type": "collectible", "x": 40, "y": 40, "point": true, "properties": [
+                { "name": "archetype", "type": "string", "value": "brute" },
+                { "name": "hit_points", "type": "int", "value": 3 } ] },
+            { "id": 3, "type": "collectible", "x": 80, "y": 80, "point": true, "properties": [
+                { "name": "archetype", "type": "string", "value": "brute" },
+                { "name": "hit_points", "type": "int", "value": 4 } ] }"#;
+        assert!(level_error(conflicting).contains("archetype 'brute' is given different properties elsewhere"));
+    }
+
+    #[test]
+    fn resource_paths_start_inside_resources() {
+        assert_eq!(resource_path(Path::new("resources/maps/../tiles/x.png")).unwrap(), "/tiles/x.png");
+        assert_eq!(
+            resource_path(Path::new("/home/me/resources/game/resources/tiles/x.png")).unwrap(),
+            "/tiles/x.png"
+        );
+        assert!(resource_path(Path::new("maps/tiles/x.png")).is_err());
+        assert!(resource_path(Path::new("resources/")).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+// One seed per run that every random part of world generation derives from, so a
+// layout can be reproduced from nothing more than the number shown on screen.
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+pub const SEED_ARG: &str = "--seed";
+pub const SEED_ENV_VAR: &str = "TOP_SEED";
+
+// Names of the sub-streams. Each one is independent of the others, so drawing more
+// numbers from one (e.g. extra placement retries) doesn't change what the rest produce.
+pub const PLACEMENT_STREAM: &str = "placement";
+pub const INITIAL_TIME_STREAM: &str = "initial_time";
+pub const NOISE_SEED_STREAM: &str = "noise_seed";
+pub const SMOKE_STREAM: &str = "smoke";
+pub const SPLIT_STREAM: &str = "split";
+pub const DENSITY_STREAM: &str = "density";
+pub const ARCHETYPE_STREAM: &str = "archetype";
+pub const EYE_STREAM: &str = "eye";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    // `--seed <value>` on the command line wins over the `TOP_SEED` environment variable.
+    // Without either a fresh random seed is picked.
+    pub fn from_args_or_env() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        let from_args = args
+            .iter()
+            .position(|arg| arg == SEED_ARG)
+            .and_then(|i| args.get(i + 1))
+            .cloned();
+        match from_args.or_else(|| std::env::var(SEED_ENV_VAR).ok()) {
+            Some(text) => WorldSeed::parse(&text),
+            None => WorldSeed(rand::thread_rng().gen()),
+        }
+    }
+
+    // Numbers are used as-is; anything else (e.g. "bugreport-12") is hashed
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        match text.parse::<u64>() {
+            Ok(seed) => WorldSeed(seed),
+            Err(_) => WorldSeed(fnv1a(text)),
+        }
+    }
+
+    // A generator for the named sub-stream. Asking twice for the same name gives two
+    // generators that produce the same numbers. ChaCha8 rather than `StdRng`, whose
+    // algorithm may change between rand releases and would quietly change every world.
+    pub fn stream(&self, name: &str) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(splitmix64(self.0 ^ fnv1a(name)))
+    }
+
+    // A seed of its own for one part of the run (e.g. a single wave), so each part
+    // gets different worlds while the whole run still replays from one number
+    pub fn child(&self, name: &str) -> WorldSeed {
+        WorldSeed(splitmix64(self.0 ^ fnv1a(name)).rotate_left(17))
+    }
+}
+
+impl std::fmt::Display for WorldSeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Stable across platforms and Rust versions, unlike `DefaultHasher`
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// Spreads nearby seeds (1, 2, 3...) far apart before they reach the generator
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw(rng: &mut ChaCha8Rng) -> Vec<u64> {
+        (0..8).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn same_stream_gives_the_same_numbers() {
+        let seed = WorldSeed(1234);
+        assert_eq!(draw(&mut seed.stream(PLACEMENT_STREAM)), draw(&mut seed.stream(PLACEMENT_STREAM)));
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let seed = WorldSeed(1234);
+        let placement = draw(&mut seed.stream(PLACEMENT_STREAM));
+        assert_ne!(placement, draw(&mut seed.stream(INITIAL_TIME_STREAM)));
+        assert_ne!(placement, draw(&mut seed.stream(ARCHETYPE_STREAM)));
+        assert_ne!(placement, draw(&mut WorldSeed(1235).stream(PLACEMENT_STREAM)));
+        assert_ne!(placement, draw(&mut seed.child("wave1").stream(PLACEMENT_STREAM)));
+    }
+
+    #[test]
+    fn text_seeds_are_hashed_and_numbers_kept() {
+        assert_eq!(WorldSeed::parse(" 99 "), WorldSeed(99));
+        assert_eq!(WorldSeed::parse("bugreport-12"), WorldSeed::parse("bugreport-12"));
+        assert_ne!(WorldSeed::parse("bugreport-12"), WorldSeed::parse("bugreport-13"));
+    }
+}